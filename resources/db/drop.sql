@@ -1 +1,2 @@
-DROP TABLE IF EXISTS players;
\ No newline at end of file
+DROP TABLE IF EXISTS players;
+DROP TABLE IF EXISTS game_state;
\ No newline at end of file
@@ -3,4 +3,17 @@ CREATE TABLE  IF NOT EXISTS players
     id  VARCHAR(255) PRIMARY KEY NOT NULL,
     name VARCHAR(255) NOT NULL UNIQUE,
     score OID NOT NULL
-)
\ No newline at end of file
+);
+
+CREATE TABLE  IF NOT EXISTS game_state
+(
+    id  VARCHAR(255) PRIMARY KEY NOT NULL,
+    started BOOLEAN NOT NULL,
+    players VARCHAR(255)[] NOT NULL,
+    remaining_questions INT[] NOT NULL,
+    current_question INT,
+    buzz_author VARCHAR(255)
+);
+
+ALTER TABLE game_state ADD COLUMN IF NOT EXISTS buzz_queue VARCHAR(255)[] NOT NULL DEFAULT '{}';
+ALTER TABLE game_state ADD COLUMN IF NOT EXISTS last_event_id BIGINT NOT NULL DEFAULT 0;
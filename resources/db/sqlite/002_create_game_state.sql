@@ -0,0 +1,9 @@
+CREATE TABLE  IF NOT EXISTS game_state
+(
+    id  VARCHAR(255) PRIMARY KEY NOT NULL,
+    started BOOLEAN NOT NULL,
+    players TEXT NOT NULL,
+    remaining_questions TEXT NOT NULL,
+    current_question INTEGER,
+    buzz_author VARCHAR(255)
+);
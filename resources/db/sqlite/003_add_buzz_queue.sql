@@ -0,0 +1 @@
+ALTER TABLE game_state ADD COLUMN buzz_queue TEXT NOT NULL DEFAULT '[]';
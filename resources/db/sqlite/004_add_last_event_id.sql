@@ -0,0 +1 @@
+ALTER TABLE game_state ADD COLUMN last_event_id INTEGER NOT NULL DEFAULT 0;
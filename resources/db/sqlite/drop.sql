@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS players;
+DROP TABLE IF EXISTS game_state;
+DROP TABLE IF EXISTS schema_migrations;
\ No newline at end of file
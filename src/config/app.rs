@@ -1,9 +1,24 @@
 use crate::config::db::DBConfig;
+use crate::config::game::GameConfig;
+use crate::config::log::LogConfig;
+use crate::config::rate_limit::RateLimitConfig;
+use crate::config::server::ServerConfig;
 
-use serde::{Deserialize, Serialize};
+use crate::errors::error::CustomError;
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::fmt::Display;
+use std::str::FromStr;
 use tokio::fs::read_to_string;
 
 const ACTIVE_PROFILE: &str = "active-profile";
+const CONFIG_DIR: &str = "BUZZ_CONFIG_DIR";
+const ENV_PREFIX: &str = "BUZZ_";
+const ENV_SEPARATOR: &str = "__";
+const PASSWORD_ENV: &str = "BUZZ_DB__PASSWORD";
+const APP_KEY: &str = "app";
 pub const CLASSPATH: &str = "./resources";
 const CONFIG_FILE_BASE_NAME: &str = "config";
 const YAML: &str = ".yaml";
@@ -19,73 +34,183 @@ struct Config {
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct AppConfig {
     pub name: String,
+    #[serde(default)]
+    pub storage: StorageKind,
+    #[serde(default)]
+    pub server: ServerConfig,
+    #[serde(default)]
+    pub game: GameConfig,
+    #[serde(default)]
+    pub log: LogConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
     pub db: DBConfig,
 }
 
+/// ##Storage backend used by the application
+///
+///  * Database : players and game state are saved into the database described by the db configuration
+///  * Memory : players and game state are kept in memory and lost on restart
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    #[default]
+    #[serde(alias = "postgres")]
+    Database,
+    Memory,
+}
+
 /// ##Init configuration
 ///
-/// Extract configuration from config file located at ./resources/config.yaml
-pub async fn init_config() -> Result<AppConfig, crate::errors::error::CustomError> {
-    let profile = std::env::var(ACTIVE_PROFILE).ok();
+/// The default config.yaml file is merged with the config file of the active profile if any,
+/// then the values are overridden by the environment variables prefixed with BUZZ_
+///
+/// The configuration directory is ./resources, unless set by the BUZZ_CONFIG_DIR environment variable
+pub async fn init_config() -> Result<AppConfig, CustomError> {
+    let dir = std::env::var(CONFIG_DIR).unwrap_or_else(|_| CLASSPATH.to_string());
 
-    let profiles = extract_looked_profiles().await;
+    let base = format!("{}/{}{}", dir, CONFIG_FILE_BASE_NAME, YAML);
+    let mut config: Value = serde_yaml::from_str(&read_to_string(base).await?)?;
 
-    let path = match profile {
-        None => format!("{}/{}.yaml", CLASSPATH, CONFIG_FILE_BASE_NAME),
-        Some(suffix) => {
-            if !profiles.contains(&suffix) {
-                panic!("Configuration file not found for profile {}", suffix);
-            }
-            format!("{}/{}-{}.yaml", CLASSPATH, CONFIG_FILE_BASE_NAME, suffix)
+    if let Ok(profile) = std::env::var(ACTIVE_PROFILE) {
+        if !extract_looked_profiles(&dir).await?.contains(&profile) {
+            return Err(CustomError::ProfileNotFoundError(profile));
         }
-    };
 
-    let config_str = read_to_string(path).await?;
+        let path = format!(
+            "{}/{}{}{}{}",
+            dir, CONFIG_FILE_BASE_NAME, SEPARATOR, profile, YAML
+        );
+
+        merge(
+            &mut config,
+            serde_yaml::from_str(&read_to_string(path).await?)?,
+        );
+    }
+
+    build_config(config, std::env::vars().collect()).await
+}
+
+/// ##Build the configuration from the merged config files
+///
+/// The values are overridden by the environment variables, then the password is read from the secrets file,
+/// unless it is given by the BUZZ_DB__PASSWORD environment variable which has precedence
+///
+/// __config__ : the merged config files
+///
+/// __vars__ : environment variables
+pub async fn build_config(
+    mut config: Value,
+    vars: Vec<(String, String)>,
+) -> Result<AppConfig, CustomError> {
+    let password_overridden = vars.iter().any(|(name, _)| name == PASSWORD_ENV);
+
+    apply_env_overrides(&mut config, vars.into_iter());
 
-    let config: Config = serde_yaml::from_str(&config_str)?;
+    let mut config: Config = serde_yaml::from_value(config)?;
+
+    if !password_overridden {
+        config.app.db.load_password_file().await?;
+    }
 
     Ok(config.app)
 }
 
-/// ##Extract profiles for which config file exists in the CLASSPATH directory
+/// ##Merge a yaml value into another one
+///
+/// Mappings are merged recursively, any other value of __overrides__ replaces the one of __base__
+pub fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Mapping(base), Value::Mapping(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// ##Override configuration values with environment variables
+///
+/// Only the variables prefixed with BUZZ_ are used, nested keys are separated by a double underscore.
+/// For example BUZZ_DB__PORT=5433 overrides the app.db.port value
+///
+/// The values are strings, the numbers and booleans fields are parsed by __from_str_or_value__
+///
+/// __vars__ : environment variables
+pub fn apply_env_overrides(config: &mut Value, vars: impl Iterator<Item = (String, String)>) {
+    for (name, value) in vars {
+        if name == CONFIG_DIR || !name.starts_with(ENV_PREFIX) {
+            continue;
+        }
+
+        let mut path = vec![Value::from(APP_KEY)];
+        path.extend(
+            name[ENV_PREFIX.len()..]
+                .split(ENV_SEPARATOR)
+                .map(|key| Value::from(key.to_lowercase())),
+        );
+
+        // the value is kept as a raw string, the numbers and booleans fields parse it
+        let value = Value::String(value);
+
+        let override_value = path.into_iter().rev().fold(value, |value, key| {
+            let mut mapping = Mapping::new();
+            mapping.insert(key, value);
+            Value::Mapping(mapping)
+        });
+
+        merge(config, override_value);
+    }
+}
+
+/// ##Deserialize a number or a boolean given as itself or as a string
+///
+/// The environment overrides are strings, a field of another type has to be parsed
+pub fn from_str_or_value<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr + serde::de::DeserializeOwned,
+    T::Err: Display,
+{
+    match Value::deserialize(deserializer)? {
+        Value::String(value) => value.parse().map_err(D::Error::custom),
+        value => serde_yaml::from_value(value).map_err(D::Error::custom),
+    }
+}
+
+/// ##Extract profiles for which config file exists in the configuration directory
 ///
 /// For example, if the config-prod.yaml and config-rec.yaml exist
 ///
 /// this method wil return ["prod", "rec"]
-async fn extract_looked_profiles() -> Vec<String> {
+///
+/// __dir__ : configuration directory
+async fn extract_looked_profiles(dir: &str) -> Result<Vec<String>, CustomError> {
     let mut profiles: Vec<String> = Vec::new();
-    let mut dir = tokio::fs::read_dir(CLASSPATH).await.unwrap();
+    let mut dir = tokio::fs::read_dir(dir).await?;
 
-    let mut next = true;
+    while let Some(entry) = dir.next_entry().await? {
+        if !entry.metadata().await?.is_file() {
+            continue;
+        }
 
-    while next {
-        match dir.next_entry().await.unwrap() {
-            None => {
-                next = false;
-            }
-            Some(entry) => {
-                let metadata = entry.metadata().await.unwrap();
-                if metadata.is_file() {
-                    match entry.file_name().into_string() {
-                        Ok(mut filename) => {
-                            if filename.starts_with(CONFIG_FILE_BASE_NAME)
-                                && filename.ends_with(YAML)
-                            {
-                                filename = filename
-                                    .replace(CONFIG_FILE_BASE_NAME, "")
-                                    .replace(YAML, "")
-                                    .replace(SEPARATOR, "");
-
-                                if !filename.is_empty() {
-                                    profiles.push(filename);
-                                }
-                            }
-                        }
-                        Err(_) => {}
-                    }
-                }
+        if let Ok(filename) = entry.file_name().into_string() {
+            let profile = filename
+                .strip_prefix(CONFIG_FILE_BASE_NAME)
+                .and_then(|name| name.strip_suffix(YAML))
+                .and_then(|name| name.strip_prefix(SEPARATOR));
+
+            if let Some(profile) = profile.filter(|profile| !profile.is_empty()) {
+                profiles.push(profile.to_string());
             }
         }
     }
-    profiles
+
+    Ok(profiles)
 }
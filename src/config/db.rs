@@ -1,24 +1,244 @@
-use serde::{Deserialize, Serialize};
+use crate::config::app::from_str_or_value;
+use crate::errors::error::CustomError;
+
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt::{Debug, Display, Formatter};
+use std::time::Duration;
+
+// Replacement of the secret values when displayed
+const REDACTED: &str = "***";
 
 /// ##Database configuration
+///
+/// For a sqlite database, only the __database__ file path is used
+///
+/// The password is never displayed, it can be read from a secrets file given by __password_file__,
+/// the BUZZ_DB__PASSWORD environment variable has precedence over the file and the configured password
+///
+/// For a postgres database, TLS is configured with :
+///
+///   __sslmode__ : TLS mode, disabled by default
+///
+///   __ca_cert__ : path of the PEM certificate authority used to verify the server
+///
+///   __client_cert__ : path of the PEM client certificate
+///
+///   __client_key__ : path of the PEM client private key
+///
+/// The connection pool is sized by __pool__ and the startup connection is retried as set by __retry__
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct DBConfig {
+    #[serde(default)]
+    pub kind: DBKind,
+    #[serde(default = "default_host")]
     pub host: String,
-    pub port: u32,
+    #[serde(default = "default_port")]
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub port: u16,
+    #[serde(default)]
     pub username: String,
-    pub password: String,
+    #[serde(default)]
+    pub password: Secret,
+    #[serde(default)]
+    pub password_file: Option<String>,
     pub database: String,
+    #[serde(default)]
+    pub sslmode: SslMode,
+    #[serde(default)]
+    pub ca_cert: Option<String>,
+    #[serde(default)]
+    pub client_cert: Option<String>,
+    #[serde(default)]
+    pub client_key: Option<String>,
+    #[serde(default)]
+    pub pool: PoolConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+/// ##Database pool configuration
+///
+/// __max_open__ : maximum number of open connections
+///
+/// __max_idle__ : maximum number of idle connections
+///
+/// __timeout_seconds__ : maximum time to wait for a connection
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoolConfig {
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub max_open: u64,
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub max_idle: u64,
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub timeout_seconds: u64,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_open: 32,
+            max_idle: 8,
+            timeout_seconds: 15,
+        }
+    }
+}
+
+/// ##Database connection retry at startup
+///
+/// __max_attempts__ : number of attempts before giving up
+///
+/// __initial_delay_ms__ : delay before the second attempt, doubled after each failure
+///
+/// __max_delay_ms__ : upper bound of the delay between two attempts
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub max_attempts: u32,
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub initial_delay_ms: u64,
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 10,
+            initial_delay_ms: 500,
+            max_delay_ms: 10_000,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// ###Delay to wait after a failed attempt
+    ///
+    /// __attempt__ : number of the failed attempt, starting at 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+
+        Duration::from_millis(
+            self.initial_delay_ms
+                .saturating_mul(factor)
+                .min(self.max_delay_ms),
+        )
+    }
+}
+
+/// ##Database engine
+///
+///  * Postgres : a postgres server
+///  * Sqlite : a sqlite database file (requires the `sqlite` feature)
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DBKind {
+    #[default]
+    Postgres,
+    Sqlite,
 }
 
-/// ToString trait implementation
-impl ToString for DBConfig {
-    /// ##ToString
+/// ##Postgres TLS mode
+///
+///  * Disable : no TLS
+///  * Prefer : TLS if supported by the server, without certificate verification
+///  * Require : TLS without certificate verification, unless a ca certificate is given
+///  * VerifyCa : TLS with server certificate verification
+///  * VerifyFull : TLS with server certificate and host name verification
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SslMode {
+    #[default]
+    Disable,
+    Prefer,
+    Require,
+    VerifyCa,
+    VerifyFull,
+}
+
+impl SslMode {
+    /// ###Check if the server certificate has to be verified
     ///
-    /// Returns the database url
-    fn to_string(&self) -> String {
-        format!(
+    /// __has_ca_cert__ : true if a ca certificate is configured
+    pub fn verify_certificate(&self, has_ca_cert: bool) -> bool {
+        match self {
+            SslMode::Disable | SslMode::Prefer => false,
+            SslMode::Require => has_ca_cert,
+            SslMode::VerifyCa | SslMode::VerifyFull => true,
+        }
+    }
+}
+
+fn default_host() -> String {
+    "localhost".to_string()
+}
+
+fn default_port() -> u16 {
+    5432
+}
+
+impl DBConfig {
+    /// ###Read the password from the secrets file if any
+    ///
+    /// The file content, without its trailing line break, replaces the configured password
+    pub async fn load_password_file(&mut self) -> Result<(), CustomError> {
+        if let Some(path) = &self.password_file {
+            let password = tokio::fs::read_to_string(path).await?;
+
+            self.password = Secret::new(password.trim_end_matches(['\r', '\n']));
+        }
+
+        Ok(())
+    }
+}
+
+/// Display trait implementation
+impl Display for DBConfig {
+    /// ##Display
+    ///
+    /// Writes the database url, with a redacted password
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
             "postgres://{}:{}@{}:{}/{}",
-            self.password, self.username, self.host, self.port, self.database
+            self.username, self.password, self.host, self.port, self.database
         )
     }
 }
+
+/// ##Secret value
+///
+/// The value is redacted when displayed, debugged or serialized, use __expose__ to read it
+#[derive(Default, PartialEq, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// ###Secret value
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Secret({})", REDACTED)
+    }
+}
+
+impl Display for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", REDACTED)
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
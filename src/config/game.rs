@@ -0,0 +1,65 @@
+use crate::config::app::from_str_or_value;
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// Shortest interval between two pings
+const MIN_PING_INTERVAL_MS: u64 = 100;
+
+/// ##Game configuration
+///
+/// __buzz_window_ms__ : time during which the buzzes are collected after the first one,
+/// the earliest buzz once adjusted by the player latency wins. 0 gives the buzz to the first request received
+///
+/// __buzz_mode__ : single buzz, or queue of the players answering in turn
+///
+/// __ping_interval_ms__ : interval between two pings sent to the websocket players to measure their latency,
+/// at least 100 ms
+///
+/// __event_history_size__ : number of the last events kept to be replayed to the reconnecting players
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub buzz_window_ms: u64,
+    pub buzz_mode: BuzzMode,
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub ping_interval_ms: u64,
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub event_history_size: usize,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            buzz_window_ms: 100,
+            buzz_mode: BuzzMode::default(),
+            ping_interval_ms: 2_000,
+            event_history_size: 100,
+        }
+    }
+}
+
+/// ##Buzz mode
+///
+///  * Single : the first player who buzzes answers, the other buzzes are rejected
+///  * Queue : every buzz is queued, the turn passes to the next player of the queue after a wrong answer
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BuzzMode {
+    #[default]
+    Single,
+    Queue,
+}
+
+impl GameConfig {
+    /// ###Buzz collection window
+    pub fn buzz_window(&self) -> Duration {
+        Duration::from_millis(self.buzz_window_ms)
+    }
+
+    /// ###Latency measurement interval
+    pub fn ping_interval(&self) -> Duration {
+        Duration::from_millis(self.ping_interval_ms.max(MIN_PING_INTERVAL_MS))
+    }
+}
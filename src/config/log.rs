@@ -0,0 +1,64 @@
+use crate::errors::error::CustomError;
+
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+/// ##Logging configuration
+///
+/// __level__ : levels of the logs, as a filter directive such as info or buzz_game=debug,warp=info
+///
+/// __format__ : pretty logs for humans, or json logs for a log collector
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    pub level: String,
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            format: LogFormat::default(),
+        }
+    }
+}
+
+/// ##Logs output format
+///
+///  * Pretty : multi-line human readable logs
+///  * Json : a json object per log, carrying the fields of its spans
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Pretty,
+    Json,
+}
+
+impl LogConfig {
+    /// ###Filter of the logs levels
+    pub fn filter(&self) -> Result<EnvFilter, CustomError> {
+        EnvFilter::try_new(&self.level)
+            .map_err(|e| CustomError::InvalidConfigError(format!("log level: {}", e)))
+    }
+}
+
+/// ##Init the logs subscriber
+///
+/// The closing of every span is logged with its duration
+///
+/// __config__ : logging configuration
+pub fn init_logging(config: &LogConfig) -> Result<(), CustomError> {
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(config.filter()?)
+        .with_span_events(FmtSpan::CLOSE);
+
+    let result = match config.format {
+        LogFormat::Pretty => builder.pretty().try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+
+    result.map_err(|e| CustomError::InvalidConfigError(format!("logging: {}", e)))
+}
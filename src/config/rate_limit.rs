@@ -0,0 +1,96 @@
+use crate::config::app::from_str_or_value;
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// ##Rate limiting of the buzz and answer requests
+///
+/// Every client ip and every player owns a token bucket, a request consumes a token.
+/// The players behind a same ip share its bucket, which is much larger than the one of a player
+///
+/// __enabled__ : false to accept every request
+///
+/// __burst__ : size of the players buckets, the number of requests of a player accepted at once
+///
+/// __refill_interval_ms__ : time for a player to get a token back
+///
+/// __ip_burst__ : size of the ip buckets, 0 to not limit the requests by ip
+///
+/// __ip_refill_interval_ms__ : time for an ip to get a token back
+///
+/// __lockout_threshold__ : number of requests rejected in a row before the client is locked out
+///
+/// __lockout_seconds__ : duration of the lockout
+///
+/// __max_clients__ : number of clients whose bucket is kept, the least recently seen one is forgotten beyond
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub enabled: bool,
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub burst: u32,
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub refill_interval_ms: u64,
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub ip_burst: u32,
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub ip_refill_interval_ms: u64,
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub lockout_threshold: u32,
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub lockout_seconds: u64,
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub max_clients: usize,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            burst: 5,
+            refill_interval_ms: 200,
+            ip_burst: 100,
+            ip_refill_interval_ms: 20,
+            lockout_threshold: 20,
+            lockout_seconds: 30,
+            max_clients: 10_000,
+        }
+    }
+}
+
+/// ##Size and refill rate of the buckets of a kind of client
+///
+/// __burst__ : size of the buckets
+///
+/// __refill_interval__ : time to get a token back
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Limit {
+    pub burst: u32,
+    pub refill_interval: Duration,
+}
+
+impl RateLimitConfig {
+    /// ###Limit of the requests of a player
+    pub fn player_limit(&self) -> Limit {
+        Limit {
+            burst: self.burst,
+            refill_interval: Duration::from_millis(self.refill_interval_ms),
+        }
+    }
+
+    /// ###Limit of the requests of an ip
+    ///
+    /// _return_ None if the requests are not limited by ip
+    pub fn ip_limit(&self) -> Option<Limit> {
+        (self.ip_burst > 0).then(|| Limit {
+            burst: self.ip_burst,
+            refill_interval: Duration::from_millis(self.ip_refill_interval_ms),
+        })
+    }
+
+    /// ###Duration of a lockout
+    pub fn lockout(&self) -> Duration {
+        Duration::from_secs(self.lockout_seconds)
+    }
+}
@@ -0,0 +1,60 @@
+use crate::config::app::from_str_or_value;
+use crate::errors::error::CustomError;
+
+use serde::{Deserialize, Serialize};
+use std::net::{SocketAddr, ToSocketAddrs};
+
+/// ##Server configuration
+///
+/// __host__ : address or host name the server binds to, 127.0.0.1 by default
+///
+/// __port__ : port the server listens on, 3030 by default
+///
+/// __cert__ : path of the PEM certificate, the server uses TLS when it is given
+///
+/// __key__ : path of the PEM private key of the certificate
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub host: String,
+    #[serde(deserialize_with = "from_str_or_value")]
+    pub port: u16,
+    pub cert: Option<String>,
+    pub key: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 3030,
+            cert: None,
+            key: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// ###Resolve the address the server binds to
+    pub fn socket_addr(&self) -> Result<SocketAddr, CustomError> {
+        (self.host.as_str(), self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| {
+                CustomError::InvalidConfigError(format!("unable to resolve host {}", self.host))
+            })
+    }
+
+    /// ###TLS certificate and key paths
+    ///
+    /// _return_ None if TLS is not configured
+    pub fn tls(&self) -> Result<Option<(&str, &str)>, CustomError> {
+        match (&self.cert, &self.key) {
+            (Some(cert), Some(key)) => Ok(Some((cert.as_str(), key.as_str()))),
+            (None, None) => Ok(None),
+            _ => Err(CustomError::InvalidConfigError(
+                "server cert and key must be given together".to_string(),
+            )),
+        }
+    }
+}
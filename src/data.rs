@@ -1,14 +1,25 @@
 pub(crate) mod db;
 pub(crate) mod entities;
+pub(crate) mod memory;
 pub(crate) mod repositories;
+#[cfg(feature = "sqlite")]
+pub(crate) mod sqlite;
+pub(crate) mod storage;
 pub(crate) mod utils;
 
 #[cfg(test)]
 mod tests {
+    use super::memory::*;
     use super::repositories::*;
-    use crate::config::app::init_config;
-    use crate::data::db::{clear_db, create_db_pool, get_connection, init_db};
-    use crate::data::entities::Player;
+    use super::storage::*;
+    use crate::config::app::{init_config, AppConfig};
+    use crate::config::db::DBConfig;
+    use crate::data::db::{
+        clear_db, create_db_pool, create_tls_connector, get_connection, init_db, init_db_with_retry,
+    };
+    use crate::data::entities::{GameState, Player};
+    use crate::errors::error::CustomError;
+    use futures_util::future::join_all;
     use rstest::*;
 
     #[fixture]
@@ -18,9 +29,9 @@ mod tests {
         let pool_clone = pool.clone();
 
         tokio::spawn(async move {
-            let connection = get_connection(&pool_clone).await.unwrap();
-            clear_db(&connection).await.unwrap();
-            init_db(&connection).await.unwrap();
+            let mut connection = get_connection(&pool_clone).await.unwrap();
+            clear_db(&mut connection).await.unwrap();
+            init_db(&mut connection).await.unwrap();
         })
         .await
         .unwrap();
@@ -121,4 +132,479 @@ mod tests {
         let option = repo.find_by(SearchAttributes::Name(name)).await.unwrap();
         assert!(option.is_some());
     }
+
+    #[rstest]
+    #[trace]
+    async fn find_by_id_test(
+        #[future]
+        #[notrace]
+        repository: PlayerRepository,
+        #[with("Ines".to_string())] player: Player,
+    ) {
+        let repo: PlayerRepository = repository.await;
+        repo.insert(&player).await.unwrap();
+        let option = repo
+            .find_by(SearchAttributes::Id(player.id.clone()))
+            .await
+            .unwrap();
+        assert_eq!(Some(player.name), option.map(|p| p.name));
+    }
+
+    #[rstest]
+    #[trace]
+    async fn insert_conflict_test(
+        #[future]
+        #[notrace]
+        repository: PlayerRepository,
+        #[with("Lina".to_string())] player: Player,
+    ) {
+        let repo: PlayerRepository = repository.await;
+
+        repo.insert(&player).await.unwrap();
+
+        let result = repo.insert(&Player::with_name(player.name.clone())).await;
+
+        assert!(matches!(
+            result,
+            Err(CustomError::PlayerAlreadyExistWithNameError(name)) if name == player.name
+        ));
+    }
+
+    #[rstest]
+    #[trace]
+    async fn add_score_test(
+        #[future]
+        #[notrace]
+        repository: PlayerRepository,
+        #[with("Remi".to_string())] player: Player,
+    ) {
+        let repo: PlayerRepository = repository.await;
+
+        assert!(matches!(
+            repo.add_score(player.name.clone(), 2).await,
+            Err(CustomError::PlayerNotFoundWithNameError(_))
+        ));
+
+        repo.insert(&player).await.unwrap();
+
+        join_all((0..10).map(|_| repo.add_score(player.name.clone(), 2))).await;
+
+        let found = repo
+            .find_by(SearchAttributes::Name(player.name.clone()))
+            .await
+            .unwrap();
+
+        assert_eq!(Some(20), found.map(|p| p.score));
+    }
+
+    #[rstest]
+    #[trace]
+    async fn list_leaderboard_delete_test(
+        #[future]
+        #[notrace]
+        repository: PlayerRepository,
+    ) {
+        let repo: PlayerRepository = repository.await;
+
+        for (name, score) in [("Joe", 3), ("Joanna", 5), ("Karl", 1), ("50%_off", 2)] {
+            repo.insert(&Player::with_name(name.to_string()))
+                .await
+                .unwrap();
+            repo.update_score(name.to_string(), score).await.unwrap();
+        }
+
+        let page = repo.list(None, Page { number: 1, size: 2 }).await.unwrap();
+        assert_eq!(
+            vec!["Joe", "Karl"],
+            page.iter().map(|p| p.name.as_str()).collect::<Vec<&str>>()
+        );
+
+        let found = repo
+            .list(
+                Some(SearchAttributes::NameLike("jo".to_string())),
+                Page {
+                    number: 0,
+                    size: 10,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(2, found.len());
+
+        assert!(!repo
+            .exist_by(SearchAttributes::NameLike("0\\%\\_x".to_string()))
+            .await
+            .unwrap());
+        assert!(repo
+            .exist_by(SearchAttributes::NameLike("%_".to_string()))
+            .await
+            .unwrap());
+
+        let best = repo.leaderboard(2).await.unwrap();
+        assert_eq!(
+            vec![("Joanna", 5), ("Joe", 3)],
+            best.iter()
+                .map(|p| (p.name.as_str(), p.score))
+                .collect::<Vec<(&str, u32)>>()
+        );
+
+        let deleted = repo
+            .delete_by(SearchAttributes::NameLike("JO".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(2, deleted);
+        assert_eq!(
+            2,
+            repo.list(
+                None,
+                Page {
+                    number: 0,
+                    size: 10
+                }
+            )
+            .await
+            .unwrap()
+            .len()
+        );
+    }
+
+    #[rstest]
+    fn create_tls_connector_test() {
+        let db: DBConfig = serde_yaml::from_str("database: buzzz\nsslmode: require").unwrap();
+
+        assert!(create_tls_connector(&db).is_ok());
+
+        let db: DBConfig =
+            serde_yaml::from_str("database: buzzz\nclient_cert: ./client.pem").unwrap();
+
+        assert!(matches!(
+            create_tls_connector(&db),
+            Err(CustomError::InvalidConfigError(_))
+        ));
+
+        let db: DBConfig =
+            serde_yaml::from_str("database: buzzz\nca_cert: ./not-found.pem").unwrap();
+
+        assert!(matches!(
+            create_tls_connector(&db),
+            Err(CustomError::DBTlsError(_))
+        ));
+    }
+
+    #[rstest]
+    async fn init_db_with_retry_fails_test() {
+        let config: AppConfig = serde_yaml::from_str(
+            "name: unreachable\ndb:\n  database: buzzz\n  port: 1\n  retry:\n    max_attempts: 2\n    initial_delay_ms: 10",
+        )
+        .unwrap();
+
+        let pool = create_db_pool(&config).unwrap();
+
+        assert!(init_db_with_retry(&pool, &config.db.retry).await.is_err());
+    }
+
+    #[fixture]
+    async fn game_repository() -> GameRepository {
+        let config = init_config().await.unwrap();
+        let pool = create_db_pool(&config).unwrap().clone();
+        let pool_clone = pool.clone();
+
+        tokio::spawn(async move {
+            let mut connection = get_connection(&pool_clone).await.unwrap();
+            clear_db(&mut connection).await.unwrap();
+            init_db(&mut connection).await.unwrap();
+        })
+        .await
+        .unwrap();
+
+        GameRepository::new(pool.clone())
+    }
+
+    #[fixture]
+    fn game_state() -> GameState {
+        GameState {
+            started: true,
+            players: vec!["Joe".to_string(), "Karl".to_string()],
+            remaining_questions: vec![3, 1],
+            current_question: Some(2),
+            buzz_author: None,
+            buzz_queue: vec![],
+            last_event_id: 12,
+        }
+    }
+
+    #[rstest]
+    #[trace]
+    async fn save_load_game_state_test(
+        #[future]
+        #[notrace]
+        game_repository: GameRepository,
+        game_state: GameState,
+    ) {
+        let repo: GameRepository = game_repository.await;
+
+        assert!(repo.load().await.unwrap().is_none());
+
+        repo.save(&game_state).await.unwrap();
+
+        let mut state = game_state.clone();
+        state.buzz_author = Some("Karl".to_string());
+        state.buzz_queue = vec!["Joe".to_string(), "Karl".to_string()];
+        repo.save(&state).await.unwrap();
+
+        assert_eq!(Some(state), repo.load().await.unwrap());
+
+        repo.clear().await.unwrap();
+
+        assert!(repo.load().await.unwrap().is_none());
+    }
+
+    #[rstest]
+    async fn game_repository_ready_test(#[future] game_repository: GameRepository) {
+        let repo: GameRepository = game_repository.await;
+
+        assert!(repo.ready().await.is_ok());
+
+        let mut connection = get_connection(&repo.db_pool).await.unwrap();
+        clear_db(&mut connection).await.unwrap();
+
+        assert!(matches!(
+            repo.ready().await,
+            Err(CustomError::NotReadyError(_))
+        ));
+
+        init_db(&mut connection).await.unwrap();
+    }
+
+    #[fixture]
+    fn memory_repository() -> MemoryPlayerRepository {
+        MemoryPlayerRepository::new()
+    }
+
+    #[rstest]
+    #[trace]
+    async fn memory_insert_find_test(
+        #[notrace] memory_repository: MemoryPlayerRepository,
+        #[with("Audrey".to_string())] player: Player,
+    ) {
+        let repo = memory_repository;
+
+        let saved_player = repo.insert(&player).await.unwrap();
+
+        assert_eq!(0, saved_player.score);
+        assert!(repo.insert(&player).await.is_err());
+
+        assert!(repo
+            .exist_by(SearchAttributes::Name(player.name.clone()))
+            .await
+            .unwrap());
+
+        let found = repo
+            .find_by(SearchAttributes::Id(player.id.clone()))
+            .await
+            .unwrap();
+
+        assert_eq!(Some(player.name.clone()), found.map(|p| p.name));
+    }
+
+    #[rstest]
+    #[trace]
+    async fn memory_update_score_test(
+        #[notrace] memory_repository: MemoryPlayerRepository,
+        #[with("Karl".to_string())] player: Player,
+    ) {
+        let repo = memory_repository;
+
+        assert!(repo.update_score(player.name.clone(), 3).await.is_err());
+
+        repo.insert(&player).await.unwrap();
+
+        let updated = repo.update_score(player.name.clone(), 3).await.unwrap();
+
+        assert_eq!(3, updated.score);
+
+        let updated = repo.add_score(player.name.clone(), 2).await.unwrap();
+
+        assert_eq!(5, updated.score);
+    }
+
+    #[rstest]
+    #[trace]
+    async fn memory_list_leaderboard_delete_test(
+        #[notrace] memory_repository: MemoryPlayerRepository,
+    ) {
+        let repo = memory_repository;
+
+        for (name, score) in [("Joe", 3), ("Joanna", 5), ("Karl", 1)] {
+            repo.insert(&Player::with_name(name.to_string()))
+                .await
+                .unwrap();
+            repo.update_score(name.to_string(), score).await.unwrap();
+        }
+
+        let page = repo.list(None, Page { number: 1, size: 2 }).await.unwrap();
+        assert_eq!(
+            vec!["Karl".to_string()],
+            page.into_iter().map(|p| p.name).collect::<Vec<String>>()
+        );
+
+        let first = repo
+            .find_by(SearchAttributes::NameLike("JO".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(Some("Joanna".to_string()), first.map(|p| p.name));
+
+        let best = repo.leaderboard(1).await.unwrap();
+        assert_eq!(5, best[0].score);
+
+        let deleted = repo
+            .delete_by(SearchAttributes::Name("Karl".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(1, deleted);
+        assert!(!repo
+            .exist_by(SearchAttributes::Name("Karl".to_string()))
+            .await
+            .unwrap());
+    }
+
+    #[rstest]
+    #[trace]
+    async fn memory_save_load_game_state_test(game_state: GameState) {
+        let repo = MemoryGameRepository::new();
+
+        assert!(repo.load().await.unwrap().is_none());
+
+        repo.save(&game_state).await.unwrap();
+
+        assert_eq!(Some(game_state), repo.load().await.unwrap());
+
+        repo.clear().await.unwrap();
+
+        assert!(repo.load().await.unwrap().is_none());
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod sqlite_tests {
+    use std::sync::{Arc, Mutex};
+
+    use rstest::*;
+    use rusqlite::Connection;
+
+    use super::entities::{GameState, Player};
+    use super::repositories::{Page, SearchAttributes};
+    use super::sqlite::*;
+    use super::storage::*;
+
+    #[fixture]
+    async fn pool() -> SqlitePool {
+        let pool = Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
+
+        init_sqlite_db(&pool).await.unwrap();
+
+        pool
+    }
+
+    #[rstest]
+    async fn init_sqlite_db_is_idempotent_test(#[future] pool: SqlitePool) {
+        let pool = pool.await;
+
+        init_sqlite_db(&pool).await.unwrap();
+
+        let version: i64 = with_connection(&pool, |connection| {
+            Ok(
+                connection.query_row("SELECT MAX(version) FROM schema_migrations", [], |row| {
+                    row.get(0)
+                })?,
+            )
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(4, version);
+    }
+
+    #[rstest]
+    async fn sqlite_player_repository_test(#[future] pool: SqlitePool) {
+        let repo = SqlitePlayerRepository::new(pool.await);
+        let player = Player::with_name("Audrey".to_string());
+
+        let saved_player = repo.insert(&player).await.unwrap();
+
+        assert_eq!(player.name, saved_player.name);
+        assert!(repo.insert(&player).await.is_err());
+
+        let updated = repo.update_score(player.name.clone(), 1).await.unwrap();
+
+        assert_eq!(1, updated.score);
+
+        let updated = repo.add_score(player.name.clone(), 3).await.unwrap();
+
+        assert_eq!(4, updated.score);
+
+        let found = repo
+            .find_by(SearchAttributes::Id(player.id.clone()))
+            .await
+            .unwrap();
+
+        assert_eq!(Some(4), found.map(|p| p.score));
+        assert!(repo.update_score("Karl".to_string(), 1).await.is_err());
+
+        repo.insert(&Player::with_name("Audrey_2".to_string()))
+            .await
+            .unwrap();
+
+        let found = repo
+            .list(
+                Some(SearchAttributes::NameLike("y_".to_string())),
+                Page {
+                    number: 0,
+                    size: 10,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(1, found.len());
+
+        let best = repo.leaderboard(1).await.unwrap();
+        assert_eq!(
+            vec![player.name.clone()],
+            best.into_iter().map(|p| p.name).collect::<Vec<String>>()
+        );
+
+        let deleted = repo
+            .delete_by(SearchAttributes::NameLike("audrey".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(2, deleted);
+    }
+
+    #[rstest]
+    async fn sqlite_game_repository_test(#[future] pool: SqlitePool) {
+        let repo = SqliteGameRepository::new(pool.await);
+
+        let state = GameState {
+            started: true,
+            players: vec!["Joe".to_string()],
+            remaining_questions: vec![3, 1],
+            current_question: Some(2),
+            buzz_author: Some("Joe".to_string()),
+            buzz_queue: vec!["Joe".to_string()],
+            last_event_id: 12,
+        };
+
+        assert!(repo.load().await.unwrap().is_none());
+
+        repo.save(&state).await.unwrap();
+        repo.save(&state).await.unwrap();
+
+        assert_eq!(Some(state), repo.load().await.unwrap());
+
+        repo.clear().await.unwrap();
+
+        assert!(repo.load().await.unwrap().is_none());
+
+        assert!(repo.ready().await.is_ok());
+    }
 }
@@ -1,29 +1,30 @@
 use crate::config::app::AppConfig;
+use crate::config::db::{DBConfig, RetryConfig, SslMode};
 use crate::errors::error::*;
 
 use mobc::{Connection, Pool};
-use mobc_postgres::tokio_postgres::{Config, NoTls};
+use mobc_postgres::tokio_postgres::config::SslMode as PgSslMode;
+use mobc_postgres::tokio_postgres::Config;
 use mobc_postgres::PgConnectionManager;
-use std::str::FromStr;
+use openssl::ssl::{SslConnector, SslFiletype, SslMethod, SslVerifyMode};
+use postgres_openssl::MakeTlsConnector;
 use std::time::Duration;
 use tokio::fs::read_to_string;
 
-// Database pool constants
-const DB_POOL_MAX_OPEN: u64 = 32;
-const DB_POOL_MAX_IDLE: u64 = 8;
-const DB_POOL_TIMEOUT_SECONDS: u64 = 15;
-
 // Sql file path
 const INIT_SQL_FILE: &str = "./resources/db/init.sql";
 const DROP_SQL_FILE: &str = "./resources/db/drop.sql";
 
 // -- Custom types
 
+/// TLS connector, only used when TLS is enabled by the sslmode
+pub type DBTls = MakeTlsConnector;
+
 /// Connection of PgConnectionManager
-pub type DBConn = Connection<PgConnectionManager<NoTls>>;
+pub type DBConn = Connection<PgConnectionManager<DBTls>>;
 
 /// Pool of PgConnectionManager
-pub type DBPool = Pool<PgConnectionManager<NoTls>>;
+pub type DBPool = Pool<PgConnectionManager<DBTls>>;
 
 //-- Methods
 
@@ -31,20 +32,84 @@ pub type DBPool = Pool<PgConnectionManager<NoTls>>;
 ///
 /// __app_config__ : application configuration
 pub fn create_db_pool(app_config: &AppConfig) -> Result<DBPool, CustomError> {
-    // Retrieve config from db url
-    let config = Config::from_str(app_config.db.to_string().as_str())?;
+    let db_config = &app_config.db;
+
+    // Build the connection config field by field, so that the password never appears in an url
+    let mut config = Config::new();
+
+    config
+        .host(&db_config.host)
+        .port(db_config.port)
+        .user(&db_config.username)
+        .dbname(&db_config.database);
+
+    if !db_config.password.expose().is_empty() {
+        config.password(db_config.password.expose());
+    }
 
-    // Initialize a NoTls Connection manager
-    let manager = PgConnectionManager::new(config, NoTls);
+    config.ssl_mode(match db_config.sslmode {
+        SslMode::Disable => PgSslMode::Disable,
+        SslMode::Prefer => PgSslMode::Prefer,
+        SslMode::Require | SslMode::VerifyCa | SslMode::VerifyFull => PgSslMode::Require,
+    });
+
+    // Initialize a Connection manager with a TLS connector matching the sslmode
+    let manager = PgConnectionManager::new(config, create_tls_connector(db_config)?);
+
+    let pool_config = &db_config.pool;
 
     // Build and return the db pool connection
     Ok(Pool::builder()
-        .max_open(DB_POOL_MAX_OPEN)
-        .max_idle(DB_POOL_MAX_IDLE)
-        .get_timeout(Some(Duration::from_secs(DB_POOL_TIMEOUT_SECONDS)))
+        .max_open(pool_config.max_open)
+        .max_idle(pool_config.max_idle)
+        .get_timeout(Some(Duration::from_secs(pool_config.timeout_seconds)))
         .build(manager))
 }
 
+/// Create the TLS connector matching the database configuration
+///
+/// __db_config__ : database configuration
+pub fn create_tls_connector(db_config: &DBConfig) -> Result<DBTls, CustomError> {
+    let mut builder = SslConnector::builder(SslMethod::tls())?;
+
+    if let Some(ca_cert) = &db_config.ca_cert {
+        builder.set_ca_file(ca_cert)?;
+    }
+
+    match (&db_config.client_cert, &db_config.client_key) {
+        (Some(cert), Some(key)) => {
+            builder.set_certificate_chain_file(cert)?;
+            builder.set_private_key_file(key, SslFiletype::PEM)?;
+            builder.check_private_key()?;
+        }
+        (None, None) => {}
+        _ => {
+            return Err(CustomError::InvalidConfigError(
+                "client_cert and client_key must be given together".to_string(),
+            ))
+        }
+    }
+
+    let verify_certificate = db_config
+        .sslmode
+        .verify_certificate(db_config.ca_cert.is_some());
+
+    if !verify_certificate {
+        builder.set_verify(SslVerifyMode::NONE);
+    }
+
+    let mut connector = MakeTlsConnector::new(builder.build());
+
+    if db_config.sslmode != SslMode::VerifyFull {
+        connector.set_callback(|config, _| {
+            config.set_verify_hostname(false);
+            Ok(())
+        });
+    }
+
+    Ok(connector)
+}
+
 /// Get connection from database pool
 ///
 /// __pool__ : database pool
@@ -54,28 +119,66 @@ pub async fn get_connection(pool: &DBPool) -> Result<DBConn, CustomError> {
 
 /// Init a database
 ///
+/// All the tables are created in a single transaction
+///
 /// __connection__ : database connection
-pub async fn init_db(connection: &DBConn) -> Result<(), CustomError> {
+pub async fn init_db(connection: &mut DBConn) -> Result<(), CustomError> {
     let init_sql = read_to_string(INIT_SQL_FILE).await?;
 
-    let _ = crate::execute_batch! {
+    crate::execute_transaction! {
         connection <- connection,
         query <- init_sql
-    };
+    }
+}
 
-    Ok(())
+/// Init a database as soon as it is reachable
+///
+/// The connection and the initialization are retried with an exponential backoff,
+/// the last error is returned once all the attempts have failed
+///
+/// __pool__ : database pool
+///
+/// __retry__ : retry configuration
+pub async fn init_db_with_retry(pool: &DBPool, retry: &RetryConfig) -> Result<(), CustomError> {
+    let mut attempt = 1;
+
+    loop {
+        let result = match get_connection(pool).await {
+            Ok(mut connection) => init_db(&mut connection).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt >= retry.max_attempts => return Err(e),
+            Err(e) => {
+                let delay = retry.delay(attempt);
+
+                tracing::warn!(
+                    attempt,
+                    max_attempts = retry.max_attempts,
+                    error = %e,
+                    retry_in = ?delay,
+                    "Database not ready"
+                );
+
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
 }
 
 /// Clear a database
 ///
+/// All the tables are dropped in a single transaction
+///
 /// __connection__ : database connection
-pub async fn clear_db(connection: &DBConn) -> Result<(), CustomError> {
+pub async fn clear_db(connection: &mut DBConn) -> Result<(), CustomError> {
     let sql = read_to_string(DROP_SQL_FILE).await?;
 
-    let _ = crate::execute_batch! {
+    crate::execute_transaction! {
         connection <- connection,
         query <- sql
-    };
-
-    Ok(())
+    }
 }
@@ -1,9 +1,10 @@
 use mobc_postgres::tokio_postgres::Row;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 /// ##Player entity representation
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone)]
 pub struct Player {
     pub id: String,
     pub name: String,
@@ -32,3 +33,45 @@ impl Player {
         }
     }
 }
+
+/// ##Game state entity representation
+///
+/// __started__ : true if the game has started
+///
+/// __players__ : names of the players taking part in the game
+///
+/// __remaining_questions__ : numbers of the questions not asked yet, in order
+///
+/// __current_question__ : number of the question currently asked
+///
+/// __buzz_author__ : name of the player who has buzzed
+///
+/// __buzz_queue__ : names of the players who have buzzed, in order, when the buzzes are queued
+///
+/// __last_event_id__ : id of the last event sent to the players, the ids go on from it once restored
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct GameState {
+    pub started: bool,
+    pub players: Vec<String>,
+    pub remaining_questions: Vec<i32>,
+    pub current_question: Option<i32>,
+    pub buzz_author: Option<String>,
+    #[serde(default)]
+    pub buzz_queue: Vec<String>,
+    #[serde(default)]
+    pub last_event_id: i64,
+}
+
+impl From<Row> for GameState {
+    fn from(row: Row) -> Self {
+        Self {
+            started: row.get("started"),
+            players: row.get("players"),
+            remaining_questions: row.get("remaining_questions"),
+            current_question: row.get("current_question"),
+            buzz_author: row.get("buzz_author"),
+            buzz_queue: row.get("buzz_queue"),
+            last_event_id: row.get("last_event_id"),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+
+use crate::data::entities::{GameState, Player};
+use crate::data::repositories::{Page, SearchAttributes};
+use crate::data::storage::{GameStorage, PlayerStorage};
+use crate::errors::error::CustomError;
+
+/// Check if a player matches a search attribute
+fn matches(player: &Player, attribute: &SearchAttributes) -> bool {
+    match attribute {
+        SearchAttributes::Name(name) => player.name.eq(name),
+        SearchAttributes::Id(id) => player.id.eq(id),
+        SearchAttributes::NameLike(part) => {
+            player.name.to_lowercase().contains(&part.to_lowercase())
+        }
+    }
+}
+
+/// ##In memory players data access layer
+///
+/// Players are indexed by name
+#[derive(Clone, Default)]
+pub struct MemoryPlayerRepository {
+    pub players: Arc<Mutex<HashMap<String, Player>>>,
+}
+
+impl MemoryPlayerRepository {
+    /// ###Create a new empty in memory player repository
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl PlayerStorage for MemoryPlayerRepository {
+    async fn insert(&self, player: &Player) -> Result<Player, CustomError> {
+        let mut players = self.players.lock().await;
+
+        if players.contains_key(&player.name) {
+            Err(CustomError::PlayerAlreadyExistWithNameError(
+                player.name.clone(),
+            ))
+        } else {
+            players.insert(player.name.clone(), player.clone());
+            Ok(player.clone())
+        }
+    }
+
+    async fn exist_by(&self, attribute: SearchAttributes) -> Result<bool, CustomError> {
+        Ok(self.find_by(attribute).await?.is_some())
+    }
+
+    async fn find_by(&self, attribute: SearchAttributes) -> Result<Option<Player>, CustomError> {
+        let players = self.players.lock().await;
+
+        Ok(match attribute {
+            SearchAttributes::Name(name) => players.get(&name).cloned(),
+            attribute => players
+                .values()
+                .filter(|p| matches(p, &attribute))
+                .min_by(|a, b| a.name.cmp(&b.name))
+                .cloned(),
+        })
+    }
+
+    async fn update_score(
+        &self,
+        player_name: String,
+        new_score: u32,
+    ) -> Result<Player, CustomError> {
+        let mut players = self.players.lock().await;
+
+        match players.get_mut(&player_name) {
+            Some(player) => {
+                player.score = new_score;
+                Ok(player.clone())
+            }
+            None => Err(CustomError::PlayerNotFoundWithNameError(player_name)),
+        }
+    }
+
+    async fn add_score(&self, player_name: String, points: u32) -> Result<Player, CustomError> {
+        let mut players = self.players.lock().await;
+
+        match players.get_mut(&player_name) {
+            Some(player) => {
+                player.score += points;
+                Ok(player.clone())
+            }
+            None => Err(CustomError::PlayerNotFoundWithNameError(player_name)),
+        }
+    }
+
+    async fn list(
+        &self,
+        attribute: Option<SearchAttributes>,
+        page: Page,
+    ) -> Result<Vec<Player>, CustomError> {
+        let players = self.players.lock().await;
+
+        let mut found = players
+            .values()
+            .filter(|p| attribute.as_ref().is_none_or(|a| matches(p, a)))
+            .cloned()
+            .collect::<Vec<Player>>();
+
+        found.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(found
+            .into_iter()
+            .skip(page.offset() as usize)
+            .take(page.size as usize)
+            .collect())
+    }
+
+    async fn leaderboard(&self, limit: u32) -> Result<Vec<Player>, CustomError> {
+        let players = self.players.lock().await;
+
+        let mut best = players.values().cloned().collect::<Vec<Player>>();
+
+        best.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+        best.truncate(limit as usize);
+
+        Ok(best)
+    }
+
+    async fn delete_by(&self, attribute: SearchAttributes) -> Result<u64, CustomError> {
+        let mut players = self.players.lock().await;
+
+        let before = players.len();
+        players.retain(|_, p| !matches(p, &attribute));
+
+        Ok((before - players.len()) as u64)
+    }
+}
+
+/// ##In memory game state data access layer
+#[derive(Clone, Default)]
+pub struct MemoryGameRepository {
+    pub state: Arc<Mutex<Option<GameState>>>,
+}
+
+impl MemoryGameRepository {
+    /// ###Create a new empty in memory game repository
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl GameStorage for MemoryGameRepository {
+    async fn save(&self, state: &GameState) -> Result<GameState, CustomError> {
+        *self.state.lock().await = Some(state.clone());
+        Ok(state.clone())
+    }
+
+    async fn load(&self) -> Result<Option<GameState>, CustomError> {
+        Ok(self.state.lock().await.clone())
+    }
+
+    async fn clear(&self) -> Result<(), CustomError> {
+        *self.state.lock().await = None;
+        Ok(())
+    }
+
+    async fn ready(&self) -> Result<(), CustomError> {
+        Ok(())
+    }
+}
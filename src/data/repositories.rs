@@ -1,18 +1,88 @@
+use async_trait::async_trait;
 use mobc_postgres::tokio_postgres::types::ToSql;
 
 use crate::data::db::{get_connection, DBPool};
-use crate::data::entities::Player;
+use crate::data::entities::{GameState, Player};
+use crate::data::storage::{GameStorage, PlayerStorage};
 use crate::errors::error::CustomError;
 
-const INSERT_QUERY: &str = "INSERT INTO players (id, name, score) VALUES ($1, $2, $3) RETURNING *";
+const INSERT_QUERY: &str = "INSERT INTO players (id, name, score) VALUES ($1, $2, $3) \
+    ON CONFLICT (name) DO NOTHING RETURNING *";
 const EXISTS_BY_ID_QUERY: &str = "SELECT exists(SELECT 1 FROM players WHERE id = $1)";
 const EXISTS_BY_NAME_QUERY: &str = "SELECT exists(SELECT 1 FROM players WHERE name = $1)";
+const EXISTS_BY_NAME_LIKE_QUERY: &str = "SELECT exists(SELECT 1 FROM players WHERE name ILIKE $1)";
+const FIND_BY_ID_QUERY: &str = "SELECT * FROM players WHERE id = $1";
 const FIND_BY_NAME_QUERY: &str = "SELECT * FROM players WHERE name = $1";
+const FIND_BY_NAME_LIKE_QUERY: &str =
+    "SELECT * FROM players WHERE name ILIKE $1 ORDER BY name LIMIT 1";
+const LIST_QUERY: &str = "SELECT * FROM players ORDER BY name LIMIT $1 OFFSET $2";
+const LIST_BY_ID_QUERY: &str =
+    "SELECT * FROM players WHERE id = $3 ORDER BY name LIMIT $1 OFFSET $2";
+const LIST_BY_NAME_QUERY: &str =
+    "SELECT * FROM players WHERE name = $3 ORDER BY name LIMIT $1 OFFSET $2";
+const LIST_BY_NAME_LIKE_QUERY: &str =
+    "SELECT * FROM players WHERE name ILIKE $3 ORDER BY name LIMIT $1 OFFSET $2";
+const LEADERBOARD_QUERY: &str = "SELECT * FROM players ORDER BY score DESC, name LIMIT $1";
+const DELETE_BY_ID_QUERY: &str = "DELETE FROM players WHERE id = $1 RETURNING *";
+const DELETE_BY_NAME_QUERY: &str = "DELETE FROM players WHERE name = $1 RETURNING *";
+const DELETE_BY_NAME_LIKE_QUERY: &str = "DELETE FROM players WHERE name ILIKE $1 RETURNING *";
 const UPDATE_SCORE_QUERY: &str = "UPDATE players SET score = $1 WHERE name = $2 RETURNING *";
+const ADD_SCORE_QUERY: &str =
+    "UPDATE players SET score = (score::BIGINT + $1::BIGINT)::OID WHERE name = $2 RETURNING *";
+const SAVE_GAME_STATE_QUERY: &str = "INSERT INTO game_state (id, started, players, remaining_questions, current_question, buzz_author, buzz_queue, last_event_id) \
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+    ON CONFLICT (id) DO UPDATE SET started = $2, players = $3, remaining_questions = $4, current_question = $5, buzz_author = $6, buzz_queue = $7, last_event_id = $8 \
+    RETURNING *";
+const FIND_GAME_STATE_QUERY: &str = "SELECT * FROM game_state WHERE id = $1";
+const DELETE_GAME_STATE_QUERY: &str = "DELETE FROM game_state WHERE id = $1 RETURNING *";
+const SCHEMA_READY_QUERY: &str =
+    "SELECT to_regclass('players') IS NOT NULL AND to_regclass('game_state') IS NOT NULL";
 
+// Id of the single game state row
+const GAME_STATE_ID: &str = "current";
+
+/// ##Player search attributes
+///
+///  * Name : exact player name
+///  * Id : player id
+///  * NameLike : part of the player name, case insensitive
+#[derive(Debug, Clone, PartialEq)]
 pub enum SearchAttributes {
     Name(String),
     Id(String),
+    NameLike(String),
+}
+
+/// ##Page of a paginated query
+///
+/// __number__ : page number, starting at 0
+///
+/// __size__ : maximum number of items in the page
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Page {
+    pub number: u32,
+    pub size: u32,
+}
+
+impl Page {
+    /// ###Number of items to skip before the page
+    pub fn offset(&self) -> u64 {
+        self.number as u64 * self.size as u64
+    }
+}
+
+/// ##Build a LIKE pattern matching names containing the given part
+///
+/// LIKE wildcards of the given part are escaped with a backslash
+///
+/// __part__ : searched part of the name
+pub fn like_pattern(part: &str) -> String {
+    let escaped = part
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("%{}%", escaped)
 }
 
 /// ##Players data access layer
@@ -28,37 +98,36 @@ impl PlayerRepository {
     pub fn new(db_pool: DBPool) -> Self {
         Self { db_pool }
     }
+}
 
+#[async_trait]
+impl PlayerStorage for PlayerRepository {
     /// ###Insert a player into database
     ///
+    /// A player with the same name is detected by the insert itself
+    ///
     /// __player__ : player to save
-    pub async fn insert(&self, player: &Player) -> Result<Player, CustomError> {
-        let exist = self
-            .exist_by(SearchAttributes::Name(player.name.clone()))
-            .await?;
-
-        if exist {
-            Err(CustomError::PlayerAlreadyExistWithNameError(
-                player.name.clone(),
-            ))
-        } else {
-            let row = crate::execute_query! {
-                pool <- &self.db_pool,
-                query <- String::from(INSERT_QUERY),
-                params <- &[&player.id, &player.name, &(player.score as u32)]
-            };
+    async fn insert(&self, player: &Player) -> Result<Player, CustomError> {
+        let row = crate::execute_query_opt! {
+            pool <- &self.db_pool,
+            query <- String::from(INSERT_QUERY),
+            params <- &[&player.id, &player.name, &player.score]
+        };
 
-            Ok(Player::from(row))
-        }
+        row.map(Player::from)
+            .ok_or_else(|| CustomError::PlayerAlreadyExistWithNameError(player.name.clone()))
     }
 
     /// ##Check player existence with a given name
     ///
     /// __name__ : searched name
-    pub async fn exist_by(&self, attribute: SearchAttributes) -> Result<bool, CustomError> {
+    async fn exist_by(&self, attribute: SearchAttributes) -> Result<bool, CustomError> {
         let (att, query) = match attribute {
             SearchAttributes::Name(name) => (name, String::from(EXISTS_BY_NAME_QUERY)),
             SearchAttributes::Id(id) => (id, String::from(EXISTS_BY_ID_QUERY)),
+            SearchAttributes::NameLike(part) => {
+                (like_pattern(&part), String::from(EXISTS_BY_NAME_LIKE_QUERY))
+            }
         };
 
         let row = crate::execute_query! {
@@ -70,16 +139,16 @@ impl PlayerRepository {
         Ok(row.get(0))
     }
 
-    /// ##Find a player with a given name
+    /// ##Find a player with a given attribute
     ///
-    /// __name__ : searched player name
-    pub async fn find_by(
-        &self,
-        attribute: SearchAttributes,
-    ) -> Result<Option<Player>, CustomError> {
+    /// __attribute__ : searched attribute, the first player by name is returned for a partial name
+    async fn find_by(&self, attribute: SearchAttributes) -> Result<Option<Player>, CustomError> {
         let (att, query) = match attribute {
             SearchAttributes::Name(name) => (name, String::from(FIND_BY_NAME_QUERY)),
-            SearchAttributes::Id(_id) => panic!("unimplemented yet!!!"),
+            SearchAttributes::Id(id) => (id, String::from(FIND_BY_ID_QUERY)),
+            SearchAttributes::NameLike(part) => {
+                (like_pattern(&part), String::from(FIND_BY_NAME_LIKE_QUERY))
+            }
         };
 
         let row = crate::execute_query_opt! {
@@ -93,30 +162,197 @@ impl PlayerRepository {
 
     /// ##Update player score
     ///
-    /// __player__ : updated player
+    /// __player_name__ : name of the player to update
+    ///
+    /// __new_score__ : the player new score
     ///
     /// _return_ the player updated
-    pub async fn update_score(
+    async fn update_score(
         &self,
         player_name: String,
         new_score: u32,
     ) -> Result<Player, CustomError> {
-        let exist = self
-            .exist_by(SearchAttributes::Name(player_name.clone()))
-            .await?;
+        let row = crate::execute_query_opt! {
+            pool <- &self.db_pool,
+            query <- String::from(UPDATE_SCORE_QUERY),
+            params <- &[&new_score, &player_name]
+        };
+
+        row.map(Player::from)
+            .ok_or(CustomError::PlayerNotFoundWithNameError(player_name))
+    }
 
-        return if exist {
-            let row = crate::execute_query! {
+    /// ##Add points to player score
+    ///
+    /// __player_name__ : name of the player to update
+    ///
+    /// __points__ : points to add
+    ///
+    /// _return_ the player updated
+    async fn add_score(&self, player_name: String, points: u32) -> Result<Player, CustomError> {
+        let row = crate::execute_query_opt! {
+            pool <- &self.db_pool,
+            query <- String::from(ADD_SCORE_QUERY),
+            params <- &[&(points as i64), &player_name]
+        };
+
+        row.map(Player::from)
+            .ok_or(CustomError::PlayerNotFoundWithNameError(player_name))
+    }
+
+    /// ##List players ordered by name
+    ///
+    /// __attribute__ : optional filter
+    ///
+    /// __page__ : requested page
+    async fn list(
+        &self,
+        attribute: Option<SearchAttributes>,
+        page: Page,
+    ) -> Result<Vec<Player>, CustomError> {
+        let limit = page.size as i64;
+        let offset = page.offset() as i64;
+
+        let rows = match attribute {
+            None => crate::execute_query_all! {
                 pool <- &self.db_pool,
-                query <- String::from(UPDATE_SCORE_QUERY),
-                params <- &[&(new_score as u32), &player_name]
-            };
+                query <- String::from(LIST_QUERY),
+                params <- &[&limit, &offset]
+            },
+            Some(attribute) => {
+                let (att, query) = match attribute {
+                    SearchAttributes::Name(name) => (name, String::from(LIST_BY_NAME_QUERY)),
+                    SearchAttributes::Id(id) => (id, String::from(LIST_BY_ID_QUERY)),
+                    SearchAttributes::NameLike(part) => {
+                        (like_pattern(&part), String::from(LIST_BY_NAME_LIKE_QUERY))
+                    }
+                };
+
+                crate::execute_query_all! {
+                    pool <- &self.db_pool,
+                    query <- query,
+                    params <- &[&limit, &offset, &att]
+                }
+            }
+        };
+
+        Ok(rows.into_iter().map(Player::from).collect())
+    }
+
+    /// ##Best players ordered by score
+    ///
+    /// __limit__ : maximum number of players returned
+    async fn leaderboard(&self, limit: u32) -> Result<Vec<Player>, CustomError> {
+        let rows = crate::execute_query_all! {
+            pool <- &self.db_pool,
+            query <- String::from(LEADERBOARD_QUERY),
+            params <- &[&(limit as i64)]
+        };
+
+        Ok(rows.into_iter().map(Player::from).collect())
+    }
 
-            Ok(Player::from(row))
+    /// ##Delete players with a given attribute
+    ///
+    /// __attribute__ : attribute of the players to delete
+    ///
+    /// _return_ the number of deleted players
+    async fn delete_by(&self, attribute: SearchAttributes) -> Result<u64, CustomError> {
+        let (att, query) = match attribute {
+            SearchAttributes::Name(name) => (name, String::from(DELETE_BY_NAME_QUERY)),
+            SearchAttributes::Id(id) => (id, String::from(DELETE_BY_ID_QUERY)),
+            SearchAttributes::NameLike(part) => {
+                (like_pattern(&part), String::from(DELETE_BY_NAME_LIKE_QUERY))
+            }
+        };
+
+        let rows = crate::execute_query_all! {
+            pool <- &self.db_pool,
+            query <- query,
+            params <- &[&att]
+        };
+
+        Ok(rows.len() as u64)
+    }
+}
+
+/// ##Game state data access layer
+#[derive(Clone)]
+pub struct GameRepository {
+    pub db_pool: DBPool,
+}
+
+impl GameRepository {
+    /// ###Create a new game repository
+    ///
+    /// __db_pool__ : database pool associated to the created repository
+    pub fn new(db_pool: DBPool) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait]
+impl GameStorage for GameRepository {
+    /// ##Save the game state, replacing the previous one
+    ///
+    /// __state__ : game state to save
+    async fn save(&self, state: &GameState) -> Result<GameState, CustomError> {
+        let row = crate::execute_query! {
+            pool <- &self.db_pool,
+            query <- String::from(SAVE_GAME_STATE_QUERY),
+            params <- &[
+                &GAME_STATE_ID,
+                &state.started,
+                &state.players,
+                &state.remaining_questions,
+                &state.current_question,
+                &state.buzz_author,
+                &state.buzz_queue,
+                &state.last_event_id
+            ]
+        };
+
+        Ok(GameState::from(row))
+    }
+
+    /// ##Load the saved game state
+    ///
+    /// _return_ None if no game is in progress
+    async fn load(&self) -> Result<Option<GameState>, CustomError> {
+        let row = crate::execute_query_opt! {
+            pool <- &self.db_pool,
+            query <- String::from(FIND_GAME_STATE_QUERY),
+            params <- &[&GAME_STATE_ID]
+        };
+
+        Ok(row.map(GameState::from))
+    }
+
+    /// ##Delete the saved game state
+    async fn clear(&self) -> Result<(), CustomError> {
+        let _ = crate::execute_query_opt! {
+            pool <- &self.db_pool,
+            query <- String::from(DELETE_GAME_STATE_QUERY),
+            params <- &[&GAME_STATE_ID]
+        };
+
+        Ok(())
+    }
+
+    /// ##Check that a connection can be got from the pool and the tables are created
+    async fn ready(&self) -> Result<(), CustomError> {
+        let row = crate::execute_query! {
+            pool <- &self.db_pool,
+            query <- String::from(SCHEMA_READY_QUERY),
+            params <- &[]
+        };
+
+        if row.get(0) {
+            Ok(())
         } else {
-            Err(CustomError::PlayerNotFoundWithNameError(
-                player_name.clone(),
+            Err(CustomError::NotReadyError(
+                "database schema is not initialized".to_string(),
             ))
-        };
+        }
     }
 }
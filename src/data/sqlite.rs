@@ -0,0 +1,403 @@
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use tokio::fs::read_to_string;
+
+use crate::config::app::AppConfig;
+use crate::data::entities::{GameState, Player};
+use crate::data::repositories::{like_pattern, Page, SearchAttributes};
+use crate::data::storage::{GameStorage, PlayerStorage};
+use crate::errors::error::CustomError;
+
+// Sql files directory
+const SQL_DIR: &str = "./resources/db/sqlite";
+const DROP_SQL_FILE: &str = "drop.sql";
+
+// Migration files, the version of a migration is its position in the list
+const MIGRATIONS: [&str; 4] = [
+    "001_create_players.sql",
+    "002_create_game_state.sql",
+    "003_add_buzz_queue.sql",
+    "004_add_last_event_id.sql",
+];
+
+const CREATE_MIGRATIONS_TABLE_QUERY: &str =
+    "CREATE TABLE IF NOT EXISTS schema_migrations (version INTEGER PRIMARY KEY NOT NULL)";
+const CURRENT_VERSION_QUERY: &str = "SELECT COALESCE(MAX(version), 0) FROM schema_migrations";
+const INSERT_VERSION_QUERY: &str = "INSERT INTO schema_migrations (version) VALUES (?1)";
+
+const INSERT_QUERY: &str = "INSERT INTO players (id, name, score) VALUES (?1, ?2, ?3) \
+    ON CONFLICT (name) DO NOTHING RETURNING *";
+const EXISTS_BY_ID_QUERY: &str = "SELECT exists(SELECT 1 FROM players WHERE id = ?1)";
+const EXISTS_BY_NAME_QUERY: &str = "SELECT exists(SELECT 1 FROM players WHERE name = ?1)";
+const EXISTS_BY_NAME_LIKE_QUERY: &str =
+    "SELECT exists(SELECT 1 FROM players WHERE name LIKE ?1 ESCAPE '\\')";
+const FIND_BY_ID_QUERY: &str = "SELECT * FROM players WHERE id = ?1";
+const FIND_BY_NAME_QUERY: &str = "SELECT * FROM players WHERE name = ?1";
+const FIND_BY_NAME_LIKE_QUERY: &str =
+    "SELECT * FROM players WHERE name LIKE ?1 ESCAPE '\\' ORDER BY name LIMIT 1";
+const LIST_QUERY: &str = "SELECT * FROM players ORDER BY name LIMIT ?1 OFFSET ?2";
+const LIST_BY_ID_QUERY: &str =
+    "SELECT * FROM players WHERE id = ?3 ORDER BY name LIMIT ?1 OFFSET ?2";
+const LIST_BY_NAME_QUERY: &str =
+    "SELECT * FROM players WHERE name = ?3 ORDER BY name LIMIT ?1 OFFSET ?2";
+const LIST_BY_NAME_LIKE_QUERY: &str =
+    "SELECT * FROM players WHERE name LIKE ?3 ESCAPE '\\' ORDER BY name LIMIT ?1 OFFSET ?2";
+const LEADERBOARD_QUERY: &str = "SELECT * FROM players ORDER BY score DESC, name LIMIT ?1";
+const DELETE_BY_ID_QUERY: &str = "DELETE FROM players WHERE id = ?1";
+const DELETE_BY_NAME_QUERY: &str = "DELETE FROM players WHERE name = ?1";
+const DELETE_BY_NAME_LIKE_QUERY: &str = "DELETE FROM players WHERE name LIKE ?1 ESCAPE '\\'";
+const UPDATE_SCORE_QUERY: &str = "UPDATE players SET score = ?1 WHERE name = ?2 RETURNING *";
+const ADD_SCORE_QUERY: &str = "UPDATE players SET score = score + ?1 WHERE name = ?2 RETURNING *";
+const SAVE_GAME_STATE_QUERY: &str = "INSERT INTO game_state (id, started, players, remaining_questions, current_question, buzz_author, buzz_queue, last_event_id) \
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8) \
+    ON CONFLICT (id) DO UPDATE SET started = ?2, players = ?3, remaining_questions = ?4, current_question = ?5, buzz_author = ?6, buzz_queue = ?7, last_event_id = ?8";
+const FIND_GAME_STATE_QUERY: &str = "SELECT * FROM game_state WHERE id = ?1";
+const DELETE_GAME_STATE_QUERY: &str = "DELETE FROM game_state WHERE id = ?1";
+const SCHEMA_READY_QUERY: &str = "SELECT count(*) FROM sqlite_master \
+    WHERE type = 'table' AND name IN ('players', 'game_state')";
+
+// Id of the single game state row
+const GAME_STATE_ID: &str = "current";
+
+// -- Custom types
+
+/// Sqlite connection shared by the repositories
+pub type SqlitePool = Arc<Mutex<Connection>>;
+
+//-- Methods
+
+/// Open the sqlite database file
+///
+/// __app_config__ : application configuration
+pub fn create_sqlite_pool(app_config: &AppConfig) -> Result<SqlitePool, CustomError> {
+    let connection = Connection::open(&app_config.db.database)?;
+
+    Ok(Arc::new(Mutex::new(connection)))
+}
+
+/// Run a blocking operation with the sqlite connection
+///
+/// __pool__ : sqlite pool
+///
+/// __operation__ : operation to run
+pub async fn with_connection<T, F>(pool: &SqlitePool, operation: F) -> Result<T, CustomError>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut Connection) -> Result<T, CustomError> + Send + 'static,
+{
+    let pool = pool.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut connection = pool.lock().unwrap();
+        operation(&mut connection)
+    })
+    .await?
+}
+
+/// Init a sqlite database by applying the migrations not applied yet
+///
+/// __pool__ : sqlite pool
+pub async fn init_sqlite_db(pool: &SqlitePool) -> Result<(), CustomError> {
+    let mut migrations = Vec::new();
+
+    for (index, file) in MIGRATIONS.iter().enumerate() {
+        let sql = read_to_string(format!("{}/{}", SQL_DIR, file)).await?;
+        migrations.push((index as i64 + 1, sql));
+    }
+
+    with_connection(pool, move |connection| {
+        connection.execute_batch(CREATE_MIGRATIONS_TABLE_QUERY)?;
+
+        let version: i64 = connection.query_row(CURRENT_VERSION_QUERY, [], |row| row.get(0))?;
+
+        for (v, sql) in migrations.into_iter().filter(|(v, _)| *v > version) {
+            let transaction = connection.transaction()?;
+            transaction.execute_batch(&sql)?;
+            transaction.execute(INSERT_VERSION_QUERY, params![v])?;
+            transaction.commit()?;
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+/// Clear a sqlite database
+///
+/// __pool__ : sqlite pool
+pub async fn clear_sqlite_db(pool: &SqlitePool) -> Result<(), CustomError> {
+    let sql = read_to_string(format!("{}/{}", SQL_DIR, DROP_SQL_FILE)).await?;
+
+    with_connection(pool, move |connection| Ok(connection.execute_batch(&sql)?)).await
+}
+
+fn player_from_row(row: &Row) -> rusqlite::Result<Player> {
+    Ok(Player {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        score: row.get("score")?,
+    })
+}
+
+/// ##Players sqlite data access layer
+#[derive(Clone)]
+pub struct SqlitePlayerRepository {
+    pub pool: SqlitePool,
+}
+
+impl SqlitePlayerRepository {
+    /// ###Create a new sqlite player repository
+    ///
+    /// __pool__ : sqlite pool associated to the created repository
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl PlayerStorage for SqlitePlayerRepository {
+    async fn insert(&self, player: &Player) -> Result<Player, CustomError> {
+        let player = player.clone();
+
+        with_connection(&self.pool, move |connection| {
+            connection
+                .query_row(
+                    INSERT_QUERY,
+                    params![player.id, player.name, player.score],
+                    player_from_row,
+                )
+                .optional()?
+                .ok_or(CustomError::PlayerAlreadyExistWithNameError(player.name))
+        })
+        .await
+    }
+
+    async fn exist_by(&self, attribute: SearchAttributes) -> Result<bool, CustomError> {
+        let (att, query) = match attribute {
+            SearchAttributes::Name(name) => (name, EXISTS_BY_NAME_QUERY),
+            SearchAttributes::Id(id) => (id, EXISTS_BY_ID_QUERY),
+            SearchAttributes::NameLike(part) => (like_pattern(&part), EXISTS_BY_NAME_LIKE_QUERY),
+        };
+
+        with_connection(&self.pool, move |connection| {
+            Ok(connection.query_row(query, params![att], |row| row.get(0))?)
+        })
+        .await
+    }
+
+    async fn find_by(&self, attribute: SearchAttributes) -> Result<Option<Player>, CustomError> {
+        let (att, query) = match attribute {
+            SearchAttributes::Name(name) => (name, FIND_BY_NAME_QUERY),
+            SearchAttributes::Id(id) => (id, FIND_BY_ID_QUERY),
+            SearchAttributes::NameLike(part) => (like_pattern(&part), FIND_BY_NAME_LIKE_QUERY),
+        };
+
+        with_connection(&self.pool, move |connection| {
+            Ok(connection
+                .query_row(query, params![att], player_from_row)
+                .optional()?)
+        })
+        .await
+    }
+
+    async fn update_score(
+        &self,
+        player_name: String,
+        new_score: u32,
+    ) -> Result<Player, CustomError> {
+        with_connection(&self.pool, move |connection| {
+            connection
+                .query_row(
+                    UPDATE_SCORE_QUERY,
+                    params![new_score, player_name],
+                    player_from_row,
+                )
+                .optional()?
+                .ok_or(CustomError::PlayerNotFoundWithNameError(player_name))
+        })
+        .await
+    }
+
+    async fn add_score(&self, player_name: String, points: u32) -> Result<Player, CustomError> {
+        with_connection(&self.pool, move |connection| {
+            connection
+                .query_row(
+                    ADD_SCORE_QUERY,
+                    params![points, player_name],
+                    player_from_row,
+                )
+                .optional()?
+                .ok_or(CustomError::PlayerNotFoundWithNameError(player_name))
+        })
+        .await
+    }
+
+    async fn list(
+        &self,
+        attribute: Option<SearchAttributes>,
+        page: Page,
+    ) -> Result<Vec<Player>, CustomError> {
+        let limit = page.size as i64;
+        let offset = page.offset() as i64;
+
+        with_connection(&self.pool, move |connection| {
+            let players = match attribute {
+                None => {
+                    let mut statement = connection.prepare(LIST_QUERY)?;
+                    let rows = statement.query_map(params![limit, offset], player_from_row)?;
+                    rows.collect::<rusqlite::Result<Vec<Player>>>()?
+                }
+                Some(attribute) => {
+                    let (att, query) = match attribute {
+                        SearchAttributes::Name(name) => (name, LIST_BY_NAME_QUERY),
+                        SearchAttributes::Id(id) => (id, LIST_BY_ID_QUERY),
+                        SearchAttributes::NameLike(part) => {
+                            (like_pattern(&part), LIST_BY_NAME_LIKE_QUERY)
+                        }
+                    };
+
+                    let mut statement = connection.prepare(query)?;
+                    let rows = statement.query_map(params![limit, offset, att], player_from_row)?;
+                    rows.collect::<rusqlite::Result<Vec<Player>>>()?
+                }
+            };
+
+            Ok(players)
+        })
+        .await
+    }
+
+    async fn leaderboard(&self, limit: u32) -> Result<Vec<Player>, CustomError> {
+        with_connection(&self.pool, move |connection| {
+            let mut statement = connection.prepare(LEADERBOARD_QUERY)?;
+            let rows = statement.query_map(params![limit], player_from_row)?;
+
+            Ok(rows.collect::<rusqlite::Result<Vec<Player>>>()?)
+        })
+        .await
+    }
+
+    async fn delete_by(&self, attribute: SearchAttributes) -> Result<u64, CustomError> {
+        let (att, query) = match attribute {
+            SearchAttributes::Name(name) => (name, DELETE_BY_NAME_QUERY),
+            SearchAttributes::Id(id) => (id, DELETE_BY_ID_QUERY),
+            SearchAttributes::NameLike(part) => (like_pattern(&part), DELETE_BY_NAME_LIKE_QUERY),
+        };
+
+        with_connection(&self.pool, move |connection| {
+            Ok(connection.execute(query, params![att])? as u64)
+        })
+        .await
+    }
+}
+
+/// ##Game state sqlite data access layer
+///
+/// Players and remaining questions are stored as json arrays
+#[derive(Clone)]
+pub struct SqliteGameRepository {
+    pub pool: SqlitePool,
+}
+
+impl SqliteGameRepository {
+    /// ###Create a new sqlite game repository
+    ///
+    /// __pool__ : sqlite pool associated to the created repository
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl GameStorage for SqliteGameRepository {
+    async fn save(&self, state: &GameState) -> Result<GameState, CustomError> {
+        let players = serde_json::to_string(&state.players)?;
+        let remaining_questions = serde_json::to_string(&state.remaining_questions)?;
+        let buzz_queue = serde_json::to_string(&state.buzz_queue)?;
+        let saved = state.clone();
+
+        with_connection(&self.pool, move |connection| {
+            connection.execute(
+                SAVE_GAME_STATE_QUERY,
+                params![
+                    GAME_STATE_ID,
+                    saved.started,
+                    players,
+                    remaining_questions,
+                    saved.current_question,
+                    saved.buzz_author,
+                    buzz_queue,
+                    saved.last_event_id
+                ],
+            )?;
+
+            Ok(saved)
+        })
+        .await
+    }
+
+    async fn load(&self) -> Result<Option<GameState>, CustomError> {
+        let row = with_connection(&self.pool, |connection| {
+            Ok(connection
+                .query_row(FIND_GAME_STATE_QUERY, params![GAME_STATE_ID], |row| {
+                    Ok((
+                        row.get::<_, bool>("started")?,
+                        row.get::<_, String>("players")?,
+                        row.get::<_, String>("remaining_questions")?,
+                        row.get::<_, Option<i32>>("current_question")?,
+                        row.get::<_, Option<String>>("buzz_author")?,
+                        row.get::<_, String>("buzz_queue")?,
+                        row.get::<_, i64>("last_event_id")?,
+                    ))
+                })
+                .optional()?)
+        })
+        .await?;
+
+        match row {
+            None => Ok(None),
+            Some((
+                started,
+                players,
+                remaining_questions,
+                current_question,
+                buzz_author,
+                buzz_queue,
+                last_event_id,
+            )) => Ok(Some(GameState {
+                started,
+                players: serde_json::from_str(&players)?,
+                remaining_questions: serde_json::from_str(&remaining_questions)?,
+                current_question,
+                buzz_author,
+                buzz_queue: serde_json::from_str(&buzz_queue)?,
+                last_event_id,
+            })),
+        }
+    }
+
+    async fn clear(&self) -> Result<(), CustomError> {
+        with_connection(&self.pool, |connection| {
+            connection.execute(DELETE_GAME_STATE_QUERY, params![GAME_STATE_ID])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn ready(&self) -> Result<(), CustomError> {
+        let tables: i64 = with_connection(&self.pool, |connection| {
+            Ok(connection.query_row(SCHEMA_READY_QUERY, [], |row| row.get(0))?)
+        })
+        .await?;
+
+        if tables == 2 {
+            Ok(())
+        } else {
+            Err(CustomError::NotReadyError(
+                "database schema is not initialized".to_string(),
+            ))
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use async_trait::async_trait;
+
+use crate::data::entities::{GameState, Player};
+use crate::data::repositories::{Page, SearchAttributes};
+use crate::errors::error::CustomError;
+
+/// ##Players storage
+#[async_trait]
+pub trait PlayerStorage: Clone + Send + Sync + 'static {
+    /// ###Insert a player
+    ///
+    /// Fails with PlayerAlreadyExistWithNameError if the name is already taken
+    ///
+    /// __player__ : player to save
+    async fn insert(&self, player: &Player) -> Result<Player, CustomError>;
+
+    /// ###Check player existence with a given attribute
+    ///
+    /// __attribute__ : searched attribute
+    async fn exist_by(&self, attribute: SearchAttributes) -> Result<bool, CustomError>;
+
+    /// ###Find a player with a given attribute
+    ///
+    /// __attribute__ : searched attribute
+    async fn find_by(&self, attribute: SearchAttributes) -> Result<Option<Player>, CustomError>;
+
+    /// ###Update player score
+    ///
+    /// __player_name__ : name of the player to update
+    ///
+    /// __new_score__ : the player new score
+    async fn update_score(
+        &self,
+        player_name: String,
+        new_score: u32,
+    ) -> Result<Player, CustomError>;
+
+    /// ###Add points to a player score in a single update
+    ///
+    /// __player_name__ : name of the player to update
+    ///
+    /// __points__ : points to add
+    async fn add_score(&self, player_name: String, points: u32) -> Result<Player, CustomError>;
+
+    /// ###List players ordered by name
+    ///
+    /// __attribute__ : optional filter
+    ///
+    /// __page__ : requested page
+    async fn list(
+        &self,
+        attribute: Option<SearchAttributes>,
+        page: Page,
+    ) -> Result<Vec<Player>, CustomError>;
+
+    /// ###Best players ordered by score, then by name
+    ///
+    /// __limit__ : maximum number of players returned
+    async fn leaderboard(&self, limit: u32) -> Result<Vec<Player>, CustomError>;
+
+    /// ###Delete players with a given attribute
+    ///
+    /// __attribute__ : attribute of the players to delete
+    ///
+    /// _return_ the number of deleted players
+    async fn delete_by(&self, attribute: SearchAttributes) -> Result<u64, CustomError>;
+}
+
+/// ##Game state storage
+#[async_trait]
+pub trait GameStorage: Clone + Send + Sync + 'static {
+    /// ###Save the game state, replacing the previous one
+    ///
+    /// __state__ : game state to save
+    async fn save(&self, state: &GameState) -> Result<GameState, CustomError>;
+
+    /// ###Load the saved game state
+    ///
+    /// _return_ None if no game is in progress
+    async fn load(&self) -> Result<Option<GameState>, CustomError>;
+
+    /// ###Delete the saved game state
+    async fn clear(&self) -> Result<(), CustomError>;
+
+    /// ###Check that the storage is reachable and its schema initialized
+    ///
+    /// Fails with NotReadyError if the schema is missing
+    async fn ready(&self) -> Result<(), CustomError>;
+}
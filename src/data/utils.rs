@@ -11,10 +11,14 @@ macro_rules! execute_query {
         let q: String = $query;
         let p: &[&(dyn Sync + ToSql)] = $params;
 
-        db_conn
-            .query_one(q.as_str(), p)
+        let span = tracing::debug_span!("db_query", query = %q);
+
+        tracing::Instrument::instrument(db_conn.query_one(q.as_str(), p), span)
             .await
-            .map_err(|source| CustomError::ExecuteDBQueryError { source, query: q })?
+            .map_err(|source| {
+                tracing::error!(query = %q, error = %source, "Database query failed");
+                CustomError::ExecuteDBQueryError { source, query: q }
+            })?
     }};
 }
 
@@ -30,10 +34,37 @@ macro_rules! execute_query_opt {
         let db_conn = get_connection(db_pool).await?;
         let q: String = $query;
         let p: &[&(dyn Sync + ToSql)] = $params;
-        db_conn
-            .query_opt(q.as_str(), p)
+        let span = tracing::debug_span!("db_query", query = %q);
+
+        tracing::Instrument::instrument(db_conn.query_opt(q.as_str(), p), span)
             .await
-            .map_err(|source| CustomError::ExecuteDBQueryError { source, query: q })?
+            .map_err(|source| {
+                tracing::error!(query = %q, error = %source, "Database query failed");
+                CustomError::ExecuteDBQueryError { source, query: q }
+            })?
+    }};
+}
+
+/// Macro rule to execute database query that returns all the resulting rows
+///
+/// __connection__ : database connection <br>
+/// __query__ : query to execute <br>
+/// __params__ : query params
+#[macro_export]
+macro_rules! execute_query_all {
+    (pool <- $pool:expr, query <- $query: expr, params <- $params: expr) => {{
+        let db_pool: &DBPool = $pool;
+        let db_conn = get_connection(db_pool).await?;
+        let q: String = $query;
+        let p: &[&(dyn Sync + ToSql)] = $params;
+        let span = tracing::debug_span!("db_query", query = %q);
+
+        tracing::Instrument::instrument(db_conn.query(q.as_str(), p), span)
+            .await
+            .map_err(|source| {
+                tracing::error!(query = %q, error = %source, "Database query failed");
+                CustomError::ExecuteDBQueryError { source, query: q }
+            })?
     }};
 }
 
@@ -50,3 +81,25 @@ macro_rules! execute_batch {
             })
     }};
 }
+
+/// Macro rule to execute a batch of queries inside a single transaction
+///
+/// __connection__ : mutable database connection <br>
+/// __query__ : queries to execute
+#[macro_export]
+macro_rules! execute_transaction {
+    (connection <- $conn: expr, query <- $query: expr) => {{
+        let c: &mut DBConn = $conn;
+        let q: String = $query;
+        let to_error = |source| CustomError::ExecuteDBQueryError {
+            source,
+            query: q.clone(),
+        };
+        let transaction = c.transaction().await.map_err(to_error)?;
+        transaction
+            .batch_execute(q.as_str())
+            .await
+            .map_err(to_error)?;
+        transaction.commit().await.map_err(to_error)
+    }};
+}
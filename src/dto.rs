@@ -1,12 +1,14 @@
 pub(crate) mod messages;
 pub(crate) mod requests;
 pub(crate) mod responses;
+pub(crate) mod schemas;
 pub(crate) mod states;
 
 #[cfg(test)]
 mod requests_tests {
 
-    use crate::dto::requests::Requests;
+    use crate::dto::requests::{Deprecation, RequestType, Requests};
+    use crate::errors::error::CustomError;
     use rstest::*;
 
     #[fixture(name = "Joe".to_string())]
@@ -30,7 +32,7 @@ mod requests_tests {
     #[rstest(add_player_json as json)]
     fn deserialize_add_player_request_test(json: String) {
         println!("{}", json);
-        let r: Requests = serde_json::from_str(json.as_str()).unwrap();
+        let r = Requests::from_json(json.as_str()).unwrap().request;
         match r {
             Requests::AddPlayer { name } => {
                 assert!(true);
@@ -42,7 +44,7 @@ mod requests_tests {
 
     #[rstest(register_buzz_json as json)]
     fn deserialize_register_buzz_request_test(json: String) {
-        let r: Requests = serde_json::from_str(json.as_str()).unwrap();
+        let r = Requests::from_json(json.as_str()).unwrap().request;
         match r {
             Requests::RegisterBuzz { player_name } => {
                 assert!(true);
@@ -54,7 +56,7 @@ mod requests_tests {
 
     #[rstest(register_answer_json as json)]
     fn deserialize_register_answer_request_test(json: String) {
-        let r = serde_json::from_str(json.as_str()).unwrap();
+        let r = Requests::from_json(json.as_str()).unwrap().request;
 
         println!("{:?}", r);
         match r {
@@ -71,18 +73,64 @@ mod requests_tests {
             _ => assert!(false),
         }
     }
+
+    #[rstest]
+    #[case(r#"{"type":"BUZZ","playerName":"Joe"}"#, RequestType::Buzz)]
+    #[case(
+        r#"{"type":"ANSWER","playerName":"Joe","questionNumber":1,"answerNumber":2}"#,
+        RequestType::Answer
+    )]
+    #[case(r#"{"type":"ADD_PLAYER","name":"Joe"}"#, RequestType::AddPlayer)]
+    // a buzz with extra fields is still a buzz
+    #[case(
+        r#"{"type":"BUZZ","playerName":"Joe","questionNumber":1}"#,
+        RequestType::Buzz
+    )]
+    #[case(r#"{"type":"BUZZ","version":1,"playerName":"Joe"}"#, RequestType::Buzz)]
+    fn deserialize_tagged_request_test(#[case] json: &str, #[case] expected: RequestType) {
+        let r = Requests::from_json(json).unwrap();
+        assert_eq!(expected, r.request.request_type());
+        assert_eq!(None, r.deprecation);
+    }
+
+    #[rstest(register_buzz_json as json)]
+    fn deserialize_untagged_request_deprecation_test(json: String) {
+        let r = Requests::from_json(json.as_str()).unwrap();
+        assert_eq!(Some(Deprecation::UntaggedRequest), r.deprecation);
+    }
+
+    #[rstest]
+    #[case(r#"{"type":"BUZZ"}"#, "playerName")]
+    #[case(r#"{"type":"JUMP","playerName":"Joe"}"#, "JUMP")]
+    #[case("not json", "expected")]
+    #[case(
+        r#"{"type":"BUZZ","version":2,"playerName":"Joe"}"#,
+        "unsupported version 2"
+    )]
+    #[case(r#"{"type":"BUZZ","version":"v1","playerName":"Joe"}"#, "version")]
+    fn deserialize_invalid_request_test(#[case] json: &str, #[case] expected: &str) {
+        match Requests::from_json(json) {
+            Err(e @ CustomError::InvalidBodyError { .. }) => {
+                assert!(e.to_string().contains(expected), "{}", e)
+            }
+            r => panic!("{:?}", r),
+        }
+    }
 }
 
 #[cfg(test)]
 mod responses_tests {
 
+    use crate::dto::messages::Messages;
     use crate::dto::responses::Response;
+    use crate::errors::error::{CustomError, ErrorCode};
     use rstest::*;
+    use std::collections::BTreeMap;
 
     #[fixture(msg = "error occurred".to_string())]
     fn error_json(msg: String) -> String {
         format!(
-            "{}\"type\":\"{}\",\"message\":\"{}\"{}",
+            "{}\"type\":\"{}\",\"message\":\"{}\",\"code\":500,\"error\":\"INTERNAL_ERROR\",\"messageKey\":\"error.internal_error\",\"params\":{{}}{}",
             "{", "ERROR", msg, "}"
         )
     }
@@ -93,10 +141,7 @@ mod responses_tests {
 
     #[fixture(msg = "error occurred".to_string())]
     fn error_response(msg: String) -> Response {
-        Response::Error {
-            message: msg,
-            code: 500,
-        }
+        Response::error(500, ErrorCode::InternalError, msg, BTreeMap::new())
     }
 
     #[rstest(error_json as json)]
@@ -115,4 +160,61 @@ mod responses_tests {
         let str = serde_json::to_string(&response).unwrap();
         assert_eq!(str, response_json(t));
     }
+
+    #[rstest]
+    fn error_message_test() {
+        let message = Messages::error(&CustomError::BuzzTakenError);
+
+        assert_eq!(
+            r#"{"error":"BUZZ_TAKEN","message":"Someone else has already buzzed","messageKey":"error.buzz_taken","params":{}}"#,
+            serde_json::to_string(&message).unwrap()
+        );
+
+        let message = Messages::error(&CustomError::WrongQuestionNumberError {
+            expected: 2,
+            found: 1,
+        });
+
+        assert!(serde_json::to_string(&message).unwrap().contains(
+            r#""messageKey":"error.wrong_question_number","params":{"expected":"2","found":"1"}"#
+        ));
+    }
+}
+
+#[cfg(test)]
+mod schemas_tests {
+
+    use crate::dto::schemas::{schema, write_schemas, SCHEMA_NAMES};
+    use rstest::*;
+
+    #[rstest]
+    fn schema_test() {
+        for name in SCHEMA_NAMES {
+            assert!(schema(name).is_some(), "{}", name);
+        }
+
+        assert!(schema("Unknown").is_none());
+
+        let json = serde_json::to_value(schema("StateChangeType").unwrap()).unwrap();
+        assert_eq!(9, json["enum"].as_array().unwrap().len());
+        assert!(json["enum"]
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!("NEW_BUZZ")));
+    }
+
+    #[rstest]
+    async fn write_schemas_test() {
+        let dir = std::env::temp_dir().join(format!("buzz-schemas-{}", uuid::Uuid::new_v4()));
+
+        write_schemas(&dir).await.unwrap();
+
+        for name in SCHEMA_NAMES {
+            let json = std::fs::read_to_string(dir.join(format!("{}.json", name))).unwrap();
+            let schema: serde_json::Value = serde_json::from_str(&json).unwrap();
+            assert!(schema["$schema"].is_string(), "{}", name);
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -1,6 +1,8 @@
+use crate::errors::error::{CustomError, ErrorCode};
+use schemars::JsonSchema;
 use serde::Serialize;
 use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::hash::{Hash, Hasher};
 
 /// ##Player answer representation
@@ -10,7 +12,7 @@ use std::hash::{Hash, Hasher};
 /// __label__ : the answer label
 ///
 /// __good__ : if answer is the good one or not
-#[derive(Serialize, Debug, Eq, Clone)]
+#[derive(Serialize, JsonSchema, Debug, Eq, Clone)]
 pub struct Answer {
     pub number: u8,
     pub label: String,
@@ -71,8 +73,24 @@ impl PartialEq for Answer {
 ///
 ///  * CanBuzz,
 ///
+/// * BuzzQueue
+///
+///     __queue__ : names of the players who have buzzed, in order
+///
+///     __current__ : name of the player whose turn it is to answer
+///
+/// * Error
+///
+///     __error__ : stable error code
+///
+///     __message__ : error message, in english
+///
+///     __message_key__ : stable key of the message, to look up its localized text
+///
+///     __params__ : values to fill in the localized message
+///
 ///  * None
-#[derive(Serialize, Debug, PartialEq, Clone, Eq)]
+#[derive(Serialize, JsonSchema, Debug, PartialEq, Clone, Eq)]
 #[serde(untagged)]
 pub enum Messages {
     Question {
@@ -105,8 +123,17 @@ pub enum Messages {
         can_buzz: bool,
     },
 
+    BuzzQueue {
+        queue: Vec<String>,
+        current: Option<String>,
+    },
+
+    #[serde(rename_all = "camelCase")]
     Error {
+        error: ErrorCode,
         message: String,
+        message_key: String,
+        params: BTreeMap<String, String>,
     },
 
     GameStart,
@@ -115,6 +142,20 @@ pub enum Messages {
 }
 
 impl Messages {
+    /// ###Error message sent to a player
+    ///
+    /// __error__ : the error to send
+    pub fn error(error: &CustomError) -> Self {
+        let code = error.code();
+
+        Messages::Error {
+            error: code,
+            message: error.to_string(),
+            message_key: code.message_key(),
+            params: error.params(),
+        }
+    }
+
     pub fn is_none(&self) -> bool {
         match self {
             Messages::None => true,
@@ -1,27 +1,55 @@
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::error::CustomError;
+
+/// Version of the request protocol, given in the `version` field of the requests
+pub const PROTOCOL_VERSION: u8 = 1;
 
 /// ##Request representation
 ///
+/// Requests are tagged with their type, `{"type": "BUZZ", "version": 1, "playerName": "Joe"}`.
+/// A request without version is read with the current version of the protocol.
 /// Request can be of 3 types:
 ///
-///  * AddPlayer
+///  * AddPlayer (ADD_PLAYER)
 ///
 ///     __name__ : name of the player to add
 ///
-///  * RegisterBuzz
+///  * RegisterBuzz (BUZZ)
 ///
 ///     __player_name__ : name of player who has buzzed
 ///
-///  * RegisterAnswer
+///  * RegisterAnswer (ANSWER)
 ///
 ///     __player_name__ : name of player who answered
 ///
 ///     __question_number__ : number of the current question
 ///
 ///     __answer_number__ : number of the answer
+#[derive(Deserialize, JsonSchema, Debug)]
+#[serde(tag = "type")]
+pub enum Requests {
+    #[serde(rename = "ANSWER", rename_all = "camelCase")]
+    RegisterAnswer {
+        player_name: String,
+        question_number: u8,
+        answer_number: u8,
+    },
+
+    #[serde(rename = "ADD_PLAYER")]
+    AddPlayer { name: String },
+
+    #[serde(rename = "BUZZ", rename_all = "camelCase")]
+    RegisterBuzz { player_name: String },
+}
+
+/// ##Request without type
+///
+/// Deprecated form of the requests, the type is guessed from the fields present
 #[derive(Deserialize, Debug)]
 #[serde(untagged)]
-pub enum Requests {
+enum UntaggedRequests {
     #[serde(rename_all = "camelCase")]
     RegisterAnswer {
         player_name: String,
@@ -39,6 +67,249 @@ pub enum Requests {
     },
 }
 
+impl From<UntaggedRequests> for Requests {
+    fn from(request: UntaggedRequests) -> Self {
+        match request {
+            UntaggedRequests::RegisterAnswer {
+                player_name,
+                question_number,
+                answer_number,
+            } => Requests::RegisterAnswer {
+                player_name,
+                question_number,
+                answer_number,
+            },
+            UntaggedRequests::AddPlayer { name } => Requests::AddPlayer { name },
+            UntaggedRequests::RegisterBuzz { player_name } => {
+                Requests::RegisterBuzz { player_name }
+            }
+        }
+    }
+}
+
+impl Requests {
+    /// ##Parse a json request
+    ///
+    /// Requests without type are still accepted, but deprecated
+    ///
+    /// __json__ : the json request
+    ///
+    /// _return_ the request, with the deprecation to report to the client if any
+    pub fn from_json(json: &str) -> Result<ReceivedRequest, CustomError> {
+        let value: serde_json::Value =
+            serde_json::from_str(json).map_err(|e| CustomError::InvalidBodyError {
+                field: None,
+                message: e.to_string(),
+            })?;
+
+        let version = match value.get("version") {
+            None => None,
+            Some(version) => Some(serde_json::from_value(version.clone()).map_err(|e| {
+                CustomError::InvalidBodyError {
+                    field: Some("version".to_string()),
+                    message: e.to_string(),
+                }
+            })?),
+        };
+        check_version(version)?;
+
+        if value.get("type").is_some() {
+            Ok(ReceivedRequest {
+                request: serde_path_to_error::deserialize(value)?,
+                deprecation: None,
+            })
+        } else {
+            let request: Requests =
+                serde_path_to_error::deserialize::<_, UntaggedRequests>(value)?.into();
+            let deprecation = deprecated_untagged(request.request_type());
+
+            Ok(ReceivedRequest {
+                request,
+                deprecation: Some(deprecation),
+            })
+        }
+    }
+
+    /// ###Type of the request
+    pub fn request_type(&self) -> RequestType {
+        match self {
+            Requests::RegisterAnswer { .. } => RequestType::Answer,
+            Requests::AddPlayer { .. } => RequestType::AddPlayer,
+            Requests::RegisterBuzz { .. } => RequestType::Buzz,
+        }
+    }
+
+    /// ###Name of the player the request is about
+    pub fn player_name(&self) -> &str {
+        match self {
+            Requests::RegisterAnswer { player_name, .. }
+            | Requests::RegisterBuzz { player_name } => player_name,
+            Requests::AddPlayer { name } => name,
+        }
+    }
+}
+
+/// ##Type of the requests
+#[derive(Deserialize, JsonSchema, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RequestType {
+    AddPlayer,
+    Buzz,
+    Answer,
+}
+
+/// ##Request received from a client
+///
+/// __request__ : the request
+///
+/// __deprecation__ : deprecated form used by the request, to report to the client
+#[derive(Debug)]
+pub struct ReceivedRequest {
+    pub request: Requests,
+    pub deprecation: Option<Deprecation>,
+}
+
+/// ##Deprecated forms of the requests
+///
+/// They are still accepted, but the client is warned they will be refused in a next version
+#[derive(Serialize, JsonSchema, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Deprecation {
+    UntaggedRequest,
+}
+
+impl Deprecation {
+    /// ###Warning sent to the client, in english
+    pub fn message(&self) -> &'static str {
+        match self {
+            Deprecation::UntaggedRequest => {
+                "Requests without type are deprecated and will be refused in a next version"
+            }
+        }
+    }
+}
+
+// Warn about a request sent without type
+fn deprecated_untagged(request_type: RequestType) -> Deprecation {
+    tracing::warn!(
+        expected_type = ?request_type,
+        "Deprecated request without type"
+    );
+
+    Deprecation::UntaggedRequest
+}
+
+/// ##Check the version of a request against the one of the protocol
+///
+/// __version__ : version given in the request, if any
+fn check_version(version: Option<u8>) -> Result<(), CustomError> {
+    match version {
+        Some(version) if version != PROTOCOL_VERSION => Err(CustomError::InvalidBodyError {
+            field: Some("version".to_string()),
+            message: format!(
+                "unsupported version {}, expected {}",
+                version, PROTOCOL_VERSION
+            ),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// ##Check the type of a request body against the one expected by the route
+///
+/// __expected__ : type of the requests handled by the route
+///
+/// __found__ : type given in the body, if any
+///
+/// _return_ the deprecation to report if the body has no type
+fn check_type(
+    expected: RequestType,
+    found: Option<RequestType>,
+) -> Result<Option<Deprecation>, CustomError> {
+    match found {
+        None => Ok(Some(deprecated_untagged(expected))),
+        Some(found) if found == expected => Ok(None),
+        Some(found) => Err(CustomError::BadRequestTypeError {
+            message: format!(
+                "Bad request : expected : {:?} , found : {:?}",
+                expected, found
+            ),
+        }),
+    }
+}
+
+/// ##Body of the buzz requests
+///
+/// __request_type__ : BUZZ, the type is optional during the deprecation of the untagged requests
+///
+/// __version__ : version of the protocol, the current one if not given
+///
+/// __player_name__ : name of player who has buzzed
+#[derive(Deserialize, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterBuzzBody {
+    #[serde(rename = "type")]
+    pub request_type: Option<RequestType>,
+    pub version: Option<u8>,
+    pub player_name: String,
+}
+
+impl TryFrom<RegisterBuzzBody> for ReceivedRequest {
+    type Error = CustomError;
+
+    fn try_from(body: RegisterBuzzBody) -> Result<Self, Self::Error> {
+        check_version(body.version)?;
+        let deprecation = check_type(RequestType::Buzz, body.request_type)?;
+
+        Ok(ReceivedRequest {
+            request: Requests::RegisterBuzz {
+                player_name: body.player_name,
+            },
+            deprecation,
+        })
+    }
+}
+
+/// ##Body of the answer requests
+///
+/// __request_type__ : ANSWER, the type is optional during the deprecation of the untagged requests
+///
+/// __version__ : version of the protocol, the current one if not given
+///
+/// __player_name__ : name of player who answered
+///
+/// __question_number__ : number of the current question
+///
+/// __answer_number__ : number of the answer
+#[derive(Deserialize, JsonSchema, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RegisterAnswerBody {
+    #[serde(rename = "type")]
+    pub request_type: Option<RequestType>,
+    pub version: Option<u8>,
+    pub player_name: String,
+    pub question_number: u8,
+    pub answer_number: u8,
+}
+
+impl TryFrom<RegisterAnswerBody> for ReceivedRequest {
+    type Error = CustomError;
+
+    fn try_from(body: RegisterAnswerBody) -> Result<Self, Self::Error> {
+        check_version(body.version)?;
+        let deprecation = check_type(RequestType::Answer, body.request_type)?;
+
+        Ok(ReceivedRequest {
+            request: Requests::RegisterAnswer {
+                player_name: body.player_name,
+                question_number: body.question_number,
+                answer_number: body.answer_number,
+            },
+            deprecation,
+        })
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct AddPlayerQuery {
     pub player: String
@@ -50,4 +321,26 @@ impl AddPlayerQuery {
             name: self.player.clone()
         }
     }
-}
\ No newline at end of file
+}
+
+/// ##Players listing query
+///
+/// __page__ : page number, starting at 0
+///
+/// __size__ : maximum number of players in the page
+///
+/// __name__ : part of the searched players name
+#[derive(Deserialize, Debug, Default)]
+pub struct ListPlayersQuery {
+    pub page: Option<u32>,
+    pub size: Option<u32>,
+    pub name: Option<String>,
+}
+
+/// ##Leaderboard query
+///
+/// __limit__ : maximum number of players in the leaderboard
+#[derive(Deserialize, Debug, Default)]
+pub struct LeaderboardQuery {
+    pub limit: Option<u32>,
+}
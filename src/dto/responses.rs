@@ -1,5 +1,10 @@
+use crate::data::entities::Player;
+use crate::dto::requests::Deprecation;
+use crate::errors::error::ErrorCode;
 use crate::Messages;
+use schemars::JsonSchema;
 use serde::Serialize;
+use std::collections::BTreeMap;
 
 /// ##Response representation
 ///
@@ -7,18 +12,100 @@ use serde::Serialize;
 ///
 ///  * Error
 ///
-///    __message__ : error message
+///    __message__ : error message, in english
+///
+///    __code__ : http status
+///
+///    __error__ : stable error code
+///
+///    __message_key__ : stable key of the message, to look up its localized text
+///
+///    __params__ : values to fill in the localized message
+///  * Warning
+///
+///    __warning__ : deprecated form used by the request
+///
+///    __message__ : warning message, in english
 ///  * GameStarted
 ///  * PlayerAdded
 ///  * BuzzRegistered
 ///  * AnswerRegistered
-#[derive(Serialize, Debug)]
+///  * Players
+///
+///    __players__ : players of the requested page
+///
+///    __page__ : page number
+///
+///    __size__ : page size
+///  * Leaderboard
+///
+///    __players__ : best players ordered by score
+///  * Alive
+///  * Ready
+#[derive(Serialize, JsonSchema, Debug)]
 #[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Response {
-    Error { message: String, code: u16 },
+    #[serde(rename_all = "camelCase")]
+    Error {
+        message: String,
+        code: u16,
+        error: ErrorCode,
+        message_key: String,
+        params: BTreeMap<String, String>,
+    },
+    Warning {
+        warning: Deprecation,
+        message: String,
+    },
     GameStarted,
     PlayerAdded(bool),
     BuzzRegistered,
     ScoreUpdated(Messages),
     AnswerRegistered,
+    Players {
+        players: Vec<Player>,
+        page: u32,
+        size: u32,
+    },
+    Leaderboard {
+        players: Vec<Player>,
+    },
+    Alive,
+    Ready,
+}
+
+impl Response {
+    /// ###Error response
+    ///
+    /// __code__ : http status
+    ///
+    /// __error__ : stable error code
+    ///
+    /// __message__ : error message, in english
+    ///
+    /// __params__ : values to fill in the localized message
+    pub fn error(
+        code: u16,
+        error: ErrorCode,
+        message: String,
+        params: BTreeMap<String, String>,
+    ) -> Self {
+        Response::Error {
+            message,
+            code,
+            error,
+            message_key: error.message_key(),
+            params,
+        }
+    }
+
+    /// ###Warning about a deprecated form of request
+    ///
+    /// __deprecation__ : deprecated form used by the request
+    pub fn warning(deprecation: Deprecation) -> Self {
+        Response::Warning {
+            warning: deprecation,
+            message: deprecation.message().to_string(),
+        }
+    }
 }
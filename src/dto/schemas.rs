@@ -0,0 +1,62 @@
+use std::path::Path;
+
+use schemars::gen::SchemaSettings;
+use schemars::schema::{RootSchema, Schema};
+use schemars::{schema_for, Map};
+
+use crate::dto::messages::Messages;
+use crate::dto::requests::{RegisterAnswerBody, RegisterBuzzBody, Requests};
+use crate::dto::responses::Response;
+use crate::dto::states::{StateChange, StateChangeType};
+use crate::errors::error::CustomError;
+
+// Names of the exported schemas
+pub const SCHEMA_NAMES: [&str; 4] = ["StateChange", "StateChangeType", "Messages", "Requests"];
+
+/// ##JSON schema of a DTO exchanged with the players
+///
+/// __name__ : name of the DTO, one of SCHEMA_NAMES
+///
+/// _return_ None if no DTO has this name
+pub fn schema(name: &str) -> Option<RootSchema> {
+    match name {
+        "StateChange" => Some(schema_for!(StateChange)),
+        "StateChangeType" => Some(schema_for!(StateChangeType)),
+        "Messages" => Some(schema_for!(Messages)),
+        "Requests" => Some(schema_for!(Requests)),
+        _ => None,
+    }
+}
+
+/// ##Component schemas of the OpenAPI description
+///
+/// Generated from the DTOs for OpenAPI 3.0, along with the schemas they refer to as `#/components/schemas/<name>`
+pub fn openapi_schemas() -> Map<String, Schema> {
+    let mut generator = SchemaSettings::openapi3().into_generator();
+
+    generator.subschema_for::<StateChange>();
+    generator.subschema_for::<Requests>();
+    generator.subschema_for::<RegisterBuzzBody>();
+    generator.subschema_for::<RegisterAnswerBody>();
+    generator.subschema_for::<Response>();
+
+    generator.take_definitions()
+}
+
+/// ##Write the JSON schemas to a directory
+///
+/// Each schema is written in a `<name>.json` file
+///
+/// __dir__ : target directory, created if it does not exist
+pub async fn write_schemas(dir: &Path) -> Result<(), CustomError> {
+    tokio::fs::create_dir_all(dir).await?;
+
+    for name in SCHEMA_NAMES {
+        if let Some(schema) = schema(name) {
+            let json = serde_json::to_string_pretty(&schema)?;
+            tokio::fs::write(dir.join(format!("{}.json", name)), json).await?;
+        }
+    }
+
+    Ok(())
+}
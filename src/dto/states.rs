@@ -1,8 +1,9 @@
+use schemars::JsonSchema;
 use serde::Serialize;
 
 use crate::dto::messages::Messages;
 
-#[derive(Serialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum StateChangeType {
     GameStart,
@@ -11,6 +12,7 @@ pub enum StateChangeType {
     NewPlayerScore,
     NewQuestion,
     NewBuzz,
+    BuzzQueue,
     NewAnswer,
     Error,
 }
@@ -26,7 +28,9 @@ pub enum StateChangeType {
 ///   __players__ : list of players names
 ///
 ///   __required_nb_players__ : the required number of player
-#[derive(Serialize, Debug, Clone)]
+///
+///   __id__ : id of the event in the game, not serialized as it is sent as the SSE event id
+#[derive(Serialize, JsonSchema, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StateChange {
     #[serde(rename = "type")]
@@ -36,6 +40,18 @@ pub struct StateChange {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub players: Vec<String>,
     pub required_nb_players: u8,
+    #[serde(skip)]
+    pub id: Option<u64>,
+}
+
+impl StateChangeType {
+    /// ###Name of the SSE event carrying a state change of this type
+    pub fn event_name(&self) -> String {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_default()
+    }
 }
 
 impl StateChange {
@@ -45,6 +61,7 @@ impl StateChange {
             message: Messages::GameStart,
             players,
             required_nb_players,
+            id: None,
         }
     }
 
@@ -54,6 +71,7 @@ impl StateChange {
             message: Messages::None,
             players: vec![],
             required_nb_players: 0,
+            id: None,
         }
     }
 
@@ -63,6 +81,7 @@ impl StateChange {
             message: Messages::CanBuzz { can_buzz },
             players: vec![],
             required_nb_players: 0,
+            id: None,
         }
     }
 
@@ -72,6 +91,7 @@ impl StateChange {
             message: score,
             players,
             required_nb_players,
+            id: None,
         }
     }
 
@@ -81,6 +101,7 @@ impl StateChange {
             message: question,
             players: vec![],
             required_nb_players: 0,
+            id: None,
         }
     }
 
@@ -90,6 +111,17 @@ impl StateChange {
             message: buzz,
             players: vec![],
             required_nb_players: 0,
+            id: None,
+        }
+    }
+
+    pub fn with_buzz_queue(queue: Messages) -> Self {
+        Self {
+            change_type: StateChangeType::BuzzQueue,
+            message: queue,
+            players: vec![],
+            required_nb_players: 0,
+            id: None,
         }
     }
 
@@ -99,15 +131,17 @@ impl StateChange {
             message: answer,
             players: vec![],
             required_nb_players: 0,
+            id: None,
         }
     }
 
-    pub fn with_error(msg: String) -> Self {
+    pub fn with_error(error: Messages) -> Self {
         Self {
             change_type: StateChangeType::Error,
-            message: Messages::Error { message: msg },
+            message: error,
             players: vec![],
             required_nb_players: 0,
+            id: None,
         }
     }
 }
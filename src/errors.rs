@@ -1 +1,68 @@
 pub(crate) mod error;
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+    use std::collections::BTreeMap;
+
+    use crate::errors::error::{CustomError, ErrorCode};
+
+    #[rstest]
+    #[case(CustomError::GameAlreadyStartedError, ErrorCode::GameAlreadyStarted)]
+    #[case(CustomError::BuzzTakenError, ErrorCode::BuzzTaken)]
+    #[case(
+        CustomError::PlayerAlreadyBuzzedError("Tom".to_string()),
+        ErrorCode::PlayerAlreadyBuzzed
+    )]
+    #[case(
+        CustomError::WrongQuestionNumberError { expected: 1, found: 0 },
+        ErrorCode::WrongQuestionNumber
+    )]
+    #[case(
+        CustomError::InvalidConfigError("port".to_string()),
+        ErrorCode::ConfigError
+    )]
+    #[case(
+        CustomError::from(serde_json::from_str::<u8>("buzz").unwrap_err()),
+        ErrorCode::InternalError
+    )]
+    fn code_test(#[case] error: CustomError, #[case] expected: ErrorCode) {
+        assert_eq!(expected, error.code());
+    }
+
+    #[rstest]
+    #[case(CustomError::BuzzTakenError, "error.buzz_taken", vec![])]
+    #[case(CustomError::GameFullError(6), "error.game_full", vec![("maxPlayers", "6")])]
+    #[case(
+        CustomError::TooManyRequestsError { retry_after: 3 },
+        "error.too_many_requests",
+        vec![("retryAfter", "3")]
+    )]
+    #[case(
+        CustomError::from(serde_json::from_str::<u8>("buzz").unwrap_err()),
+        "error.internal_error",
+        vec![]
+    )]
+    fn message_key_and_params_test(
+        #[case] error: CustomError,
+        #[case] expected_key: &str,
+        #[case] expected_params: Vec<(&str, &str)>,
+    ) {
+        assert_eq!(expected_key, error.code().message_key());
+        assert_eq!(
+            expected_params
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<BTreeMap<String, String>>(),
+            error.params()
+        );
+    }
+
+    #[rstest]
+    fn code_serialization_test() {
+        assert_eq!(
+            r#""GAME_ALREADY_STARTED""#,
+            serde_json::to_string(&ErrorCode::GameAlreadyStarted).unwrap()
+        );
+    }
+}
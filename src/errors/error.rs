@@ -1,4 +1,8 @@
+use std::collections::BTreeMap;
+
 use mobc_postgres::tokio_postgres;
+use schemars::JsonSchema;
+use serde::Serialize;
 use thiserror::Error;
 use warp::reject::Reject;
 
@@ -16,20 +20,204 @@ pub enum CustomError {
         source: tokio_postgres::Error,
         query: String,
     },
+    #[cfg(feature = "sqlite")]
+    #[error("Error occurred while executing sqlite query: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+    #[error("Error occurred while running blocking database task: {0}")]
+    BlockingTaskError(#[from] tokio::task::JoinError),
+    #[error("Error occurred while configuring database TLS: {0}")]
+    DBTlsError(#[from] openssl::error::ErrorStack),
+    #[error("Invalid configuration: {0}")]
+    InvalidConfigError(String),
+    #[error("Configuration file not found for profile {0}")]
+    ProfileNotFoundError(String),
+    #[error("Database kind {0} is not supported by this build")]
+    UnsupportedDBKindError(String),
     #[error("Error occurred while opening file: {0}")]
     OpenFileError(#[from] std::io::Error),
     #[error("Error occurred while reading file: {0}")]
     ReadFileError(#[from] std::string::FromUtf8Error),
     #[error("Error occurred deserializing yaml file: {0}")]
     YamlDeserializationError(#[from] serde_yaml::Error),
+    #[error("Error occurred (de)serializing json: {0}")]
+    JsonError(#[from] serde_json::Error),
     #[error("Error occurred while trying to insert player with name {0}")]
     PlayerAlreadyExistWithNameError(String),
     #[error("Error occurred while searching player with id {0}")]
     PlayerNotFoundWithNameError(String),
-    #[error("Error occurred while sending event message")]
-    SendEventError(#[from] tokio::sync::mpsc::error::SendError<StateChange>),
+    #[error("Player {0} is already connected")]
+    PlayerAlreadyConnectedError(String),
+    #[error("Game is already started")]
+    GameAlreadyStartedError,
+    #[error("Game is full, maximum {0} players")]
+    GameFullError(u8),
+    #[error("Someone else has already buzzed")]
+    BuzzTakenError,
+    #[error("Player {0} has already buzzed")]
+    PlayerAlreadyBuzzedError(String),
+    #[error("Player {0} is not the buzz author")]
+    NotBuzzAuthorError(String),
+    #[error("Player {found} can not send requests on the connection of player {expected}")]
+    PlayerMismatchError { expected: String, found: String },
+    #[error("Bad question number! Expected :{expected} ; found {found}")]
+    WrongQuestionNumberError { expected: u8, found: u8 },
+    #[error("No question is currently asked")]
+    NoCurrentQuestionError,
+    #[error("Error occurred while sending event message: {0}")]
+    SendEventError(String),
+    #[error("Error occurred while encoding metrics: {0}")]
+    MetricsError(String),
+    #[error("Service is not ready: {0}")]
+    NotReadyError(String),
+    #[error("Too many requests, retry after {retry_after} seconds")]
+    TooManyRequestsError { retry_after: u64 },
     #[error("Error occurred while tring to send bad message type")]
     BadRequestTypeError { message: String },
+    #[error(
+        "Invalid request body{}: {message}",
+        .field.as_ref().map(|f| format!(" at field {}", f)).unwrap_or_default()
+    )]
+    InvalidBodyError {
+        field: Option<String>,
+        message: String,
+    },
 }
 
 impl Reject for CustomError {}
+
+// The state change which could not be sent is dropped, keeping the error small
+impl From<tokio::sync::mpsc::error::SendError<StateChange>> for CustomError {
+    fn from(e: tokio::sync::mpsc::error::SendError<StateChange>) -> Self {
+        CustomError::SendEventError(e.to_string())
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for CustomError {
+    fn from(e: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        // the path is "." when the error is not related to a field, a missing field for instance
+        let field = e.path().to_string();
+
+        CustomError::InvalidBodyError {
+            field: Some(field).filter(|f| f != "."),
+            message: e.into_inner().to_string(),
+        }
+    }
+}
+
+/// ##Stable error code
+///
+/// Sent with every error payload so that the clients match on it rather than on the message,
+/// the message being only a default english description
+#[derive(Serialize, JsonSchema, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    DatabaseError,
+    ConfigError,
+    InvalidBody,
+    InvalidQuery,
+    BadRequest,
+    NotFound,
+    MethodNotAllowed,
+    UnsupportedMediaType,
+    PayloadTooLarge,
+    PlayerAlreadyExists,
+    PlayerNotFound,
+    PlayerAlreadyConnected,
+    GameAlreadyStarted,
+    GameFull,
+    BuzzTaken,
+    PlayerAlreadyBuzzed,
+    NotBuzzAuthor,
+    PlayerMismatch,
+    WrongQuestionNumber,
+    NoCurrentQuestion,
+    SendEventFailed,
+    NotReady,
+    TooManyRequests,
+    InternalError,
+}
+
+impl CustomError {
+    /// ###Stable code of the error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            CustomError::CreateDBPoolError(_)
+            | CustomError::GetDBConnectionError(_)
+            | CustomError::ExecuteDBQueryError { .. }
+            | CustomError::BlockingTaskError(_)
+            | CustomError::DBTlsError(_) => ErrorCode::DatabaseError,
+            #[cfg(feature = "sqlite")]
+            CustomError::SqliteError(_) => ErrorCode::DatabaseError,
+            CustomError::InvalidConfigError(_)
+            | CustomError::ProfileNotFoundError(_)
+            | CustomError::UnsupportedDBKindError(_)
+            | CustomError::OpenFileError(_)
+            | CustomError::ReadFileError(_)
+            | CustomError::YamlDeserializationError(_) => ErrorCode::ConfigError,
+            CustomError::BadRequestTypeError { .. } => ErrorCode::BadRequest,
+            CustomError::InvalidBodyError { .. } => ErrorCode::InvalidBody,
+            CustomError::PlayerAlreadyExistWithNameError(_) => ErrorCode::PlayerAlreadyExists,
+            CustomError::PlayerNotFoundWithNameError(_) => ErrorCode::PlayerNotFound,
+            CustomError::PlayerAlreadyConnectedError(_) => ErrorCode::PlayerAlreadyConnected,
+            CustomError::GameAlreadyStartedError => ErrorCode::GameAlreadyStarted,
+            CustomError::GameFullError(_) => ErrorCode::GameFull,
+            CustomError::BuzzTakenError => ErrorCode::BuzzTaken,
+            CustomError::PlayerAlreadyBuzzedError(_) => ErrorCode::PlayerAlreadyBuzzed,
+            CustomError::NotBuzzAuthorError(_) => ErrorCode::NotBuzzAuthor,
+            CustomError::PlayerMismatchError { .. } => ErrorCode::PlayerMismatch,
+            CustomError::WrongQuestionNumberError { .. } => ErrorCode::WrongQuestionNumber,
+            CustomError::NoCurrentQuestionError => ErrorCode::NoCurrentQuestion,
+            CustomError::SendEventError(_) => ErrorCode::SendEventFailed,
+            CustomError::JsonError(_) | CustomError::MetricsError(_) => ErrorCode::InternalError,
+            CustomError::NotReadyError(_) => ErrorCode::NotReady,
+            CustomError::TooManyRequestsError { .. } => ErrorCode::TooManyRequests,
+        }
+    }
+
+    /// ###Values of the error, to fill in its localized message
+    ///
+    /// Only the values meant for the players are given, the internal errors have none
+    pub fn params(&self) -> BTreeMap<String, String> {
+        let params: Vec<(&str, String)> = match self {
+            CustomError::PlayerAlreadyExistWithNameError(player)
+            | CustomError::PlayerNotFoundWithNameError(player)
+            | CustomError::PlayerAlreadyConnectedError(player)
+            | CustomError::PlayerAlreadyBuzzedError(player)
+            | CustomError::NotBuzzAuthorError(player) => vec![("player", player.clone())],
+            CustomError::GameFullError(max_players) => {
+                vec![("maxPlayers", max_players.to_string())]
+            }
+            CustomError::PlayerMismatchError { expected, found } => {
+                vec![("expected", expected.clone()), ("found", found.clone())]
+            }
+            CustomError::WrongQuestionNumberError { expected, found } => vec![
+                ("expected", expected.to_string()),
+                ("found", found.to_string()),
+            ],
+            CustomError::TooManyRequestsError { retry_after } => {
+                vec![("retryAfter", retry_after.to_string())]
+            }
+            CustomError::InvalidBodyError {
+                field: Some(field), ..
+            } => vec![("field", field.clone())],
+            _ => vec![],
+        };
+
+        params
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect()
+    }
+}
+
+impl ErrorCode {
+    /// ###Stable key of the error message, such as error.buzz_taken, to look up its localized text
+    pub fn message_key(&self) -> String {
+        let code = serde_json::to_value(self)
+            .ok()
+            .and_then(|code| code.as_str().map(str::to_lowercase))
+            .unwrap_or_default();
+
+        format!("error.{}", code)
+    }
+}
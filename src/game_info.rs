@@ -1,17 +1,24 @@
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::vec::IntoIter;
 
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::Mutex;
+use tracing::{debug, info, info_span, Span};
 
+use crate::config::game::{BuzzMode, GameConfig};
+use crate::data::entities::GameState;
 use crate::dto::messages::{Answer, Messages};
+use crate::errors::error::CustomError;
+use crate::metrics::{observe_reaction_time, METRICS};
 use crate::StateChange;
 
 #[derive(Debug)]
 pub struct GameInfo {
     pub started: AtomicBool,
+    pub ended: AtomicBool,
     pub buzzed: AtomicBool,
     pub buzz_author: Option<String>,
     pub max_players: u8,
@@ -20,12 +27,49 @@ pub struct GameInfo {
     pub current_question: Option<(Messages, Answer)>,
     pub senders: Arc<Mutex<HashMap<String, UnboundedSender<StateChange>>>>,
     pub questions_iterator: IntoIter<Messages>,
+    pub question_bank_size: usize,
+    pub awaited_players: HashSet<String>,
+    pub buzz_window: Duration,
+    pub ping_interval: Duration,
+    pub latencies: Arc<Mutex<HashMap<String, Duration>>>,
+    pub pending_buzzes: Vec<BuzzCandidate>,
+    pub buzz_mode: BuzzMode,
+    pub buzz_queue: Vec<String>,
+    pub last_event_id: AtomicU64,
+    pub history: Arc<Mutex<VecDeque<StateChange>>>,
+    pub history_size: usize,
+    pub buzz_opened_at: Option<Instant>,
+    pub span: Span,
+}
+
+/// ##Buzz collected during the buzz window
+///
+/// __player_name__ : name of player who has buzzed
+///
+/// __received_at__ : time the buzz has been received
+///
+/// __adjustment__ : latency compensation of the buzz
+#[derive(Debug, Clone)]
+pub struct BuzzCandidate {
+    pub player_name: String,
+    pub received_at: Instant,
+    pub adjustment: Duration,
+}
+
+impl BuzzCandidate {
+    /// ###Estimated time the player has pressed the buzz
+    pub fn pressed_at(&self) -> Instant {
+        self.received_at
+            .checked_sub(self.adjustment)
+            .unwrap_or(self.received_at)
+    }
 }
 
 impl GameInfo {
     pub fn new(questions: Vec<Messages>) -> Self {
         Self {
             started: AtomicBool::new(false),
+            ended: AtomicBool::new(false),
             buzzed: AtomicBool::new(false),
             buzz_author: None,
             max_players: 6,
@@ -33,23 +77,208 @@ impl GameInfo {
             number_of_players: AtomicU8::new(0),
             current_question: None,
             senders: Arc::new(Mutex::new(HashMap::new())),
+            question_bank_size: questions.len(),
             questions_iterator: questions.into_iter(),
+            awaited_players: HashSet::new(),
+            buzz_window: Duration::ZERO,
+            ping_interval: GameConfig::default().ping_interval(),
+            latencies: Arc::new(Mutex::new(HashMap::new())),
+            pending_buzzes: vec![],
+            buzz_mode: BuzzMode::default(),
+            buzz_queue: vec![],
+            last_event_id: AtomicU64::new(0),
+            history: Arc::new(Mutex::new(VecDeque::new())),
+            history_size: GameConfig::default().event_history_size,
+            buzz_opened_at: None,
+            span: info_span!("game", question = tracing::field::Empty),
+        }
+    }
+
+    /// ##Apply the game configuration
+    ///
+    /// __config__ : buzz window, latency measurement and event history settings
+    pub fn configure(&mut self, config: &GameConfig) {
+        self.buzz_window = config.buzz_window();
+        self.ping_interval = config.ping_interval();
+        self.buzz_mode = config.buzz_mode;
+        self.history_size = config.event_history_size;
+    }
+
+    /// ##Restore a game from a saved state
+    ///
+    /// __questions__ : the whole question bank
+    ///
+    /// __state__ : the saved game state
+    ///
+    /// Players of the saved game have to reconnect to receive events again
+    pub fn restore(questions: Vec<Messages>, state: GameState) -> Self {
+        let find_question = |number: i32| {
+            questions
+                .iter()
+                .find(|q| question_number(q) == Some(number))
+                .cloned()
+        };
+
+        let remaining = state
+            .remaining_questions
+            .iter()
+            .filter_map(|n| find_question(*n))
+            .collect::<Vec<Messages>>();
+
+        let mut game_info = GameInfo::new(remaining);
+        game_info.question_bank_size = questions.len();
+
+        game_info.started.store(state.started, Ordering::Relaxed);
+        game_info
+            .number_of_players
+            .store(state.players.len() as u8, Ordering::Relaxed);
+        game_info.awaited_players = state.players.into_iter().collect();
+
+        if let Some(question) = state.current_question.and_then(find_question) {
+            if let Some(number) = question_number(&question) {
+                game_info.span.record("question", number);
+            }
+
+            if let Some(answer) = good_answer(&question) {
+                game_info.load_current_question(question, answer);
+            }
+        }
+
+        if let Some(author) = state.buzz_author {
+            game_info.buzzed.store(true, Ordering::Relaxed);
+            game_info.buzz_author = Some(author);
+        }
+
+        game_info.buzz_queue = state.buzz_queue;
+        game_info
+            .last_event_id
+            .store(state.last_event_id as u64, Ordering::Relaxed);
+
+        info!(
+            parent: &game_info.span,
+            started = state.started,
+            players = game_info.awaited_players.len(),
+            "Game restored"
+        );
+
+        game_info
+    }
+
+    /// ##Take a snapshot of the game state to save
+    pub async fn snapshot(&self) -> GameState {
+        GameState {
+            started: self.started.load(Ordering::Relaxed),
+            players: self.players().await,
+            remaining_questions: self
+                .questions_iterator
+                .as_slice()
+                .iter()
+                .filter_map(question_number)
+                .collect(),
+            current_question: self
+                .current_question
+                .as_ref()
+                .and_then(|(q, _)| question_number(q)),
+            buzz_author: self.buzz_author.clone(),
+            buzz_queue: self.buzz_queue.clone(),
+            last_event_id: self.last_event_id.load(Ordering::Relaxed) as i64,
         }
     }
 
+    /// ##Names of all the players of the game, connected or awaited
+    pub async fn players(&self) -> Vec<String> {
+        let senders = self.senders.lock().await;
+
+        senders
+            .keys()
+            .chain(self.awaited_players.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// ##Check if a player of a restored game has not reconnected yet
+    ///
+    /// __name__ : the player name
+    pub fn is_awaited(&self, name: &str) -> bool {
+        self.awaited_players.contains(name)
+    }
+
+    /// ##Reconnect a player of a restored game
+    ///
+    /// If the game is started, the player receives the game start, the current question and the
+    /// buzz state before being registered back for the broadcast events
+    ///
+    /// __name__ : the player name
+    ///
+    /// __tx__ : event sender for the player to reconnect
+    pub async fn reconnect_player(
+        &mut self,
+        name: String,
+        tx: UnboundedSender<StateChange>,
+    ) -> Result<(), CustomError> {
+        // a player of a game saved in the lobby only gets back its seat
+        if self.started.load(Ordering::Relaxed) {
+            tx.send(StateChange::start(self.players().await, self.min_players))?;
+
+            if let Some((question, _)) = self.current_question.clone() {
+                tx.send(StateChange::with_question(question))?;
+            }
+
+            tx.send(StateChange::with_can_buzz(self.can_buzz(&name)))?;
+
+            if !self.buzz_queue.is_empty() {
+                tx.send(StateChange::with_buzz_queue(self.buzz_queue_message()))?;
+            }
+        }
+
+        self.awaited_players.remove(&name);
+        info!(parent: &self.span, player = %name, "Player reconnected");
+        self.senders.lock().await.insert(name, tx);
+
+        Ok(())
+    }
+
     pub async fn start(&self) {
         if self.number_of_players.load(Ordering::Relaxed) >= self.min_players
             && !self.started.load(Ordering::Relaxed)
         {
             self.started.store(true, Ordering::Relaxed);
+            info!(
+                parent: &self.span,
+                players = self.number_of_players.load(Ordering::Relaxed),
+                "Game started"
+            );
         }
     }
 
+    /// ##Check if the game is started and not ended
+    pub fn is_active(&self) -> bool {
+        self.started.load(Ordering::Relaxed) && !self.ended.load(Ordering::Relaxed)
+    }
+
+    /// ##Number of players still connected
+    ///
+    /// The sender of a player is closed once its event stream or its websocket is dropped
+    pub async fn connected_players(&self) -> usize {
+        self.senders
+            .lock()
+            .await
+            .values()
+            .filter(|tx| !tx.is_closed())
+            .count()
+    }
+
+    /// ##Check if the maximum number of players is reached
+    pub fn is_full(&self) -> bool {
+        self.number_of_players.load(Ordering::Relaxed) >= self.max_players
+    }
+
     pub async fn add_player(&mut self, name: String, tx: UnboundedSender<StateChange>) -> bool {
         let mut senders = self.senders.lock().await;
 
         if !self.started.load(Ordering::Relaxed) && senders.get(name.clone().as_str()).is_none() {
             senders.insert(name.clone(), tx);
+            info!(parent: &self.span, player = %name, "Player joined");
 
             let nb = self.number_of_players.load(Ordering::Relaxed);
 
@@ -75,8 +304,38 @@ impl GameInfo {
         }
     }
 
+    /// ##Check if a player can buzz
+    ///
+    /// With queued buzzes, a player can buzz once per question, otherwise only the first buzz is accepted
+    ///
+    /// __player_name__ : name of player who wants to buzz
+    pub fn can_buzz(&self, player_name: &str) -> bool {
+        match self.buzz_mode {
+            BuzzMode::Single => !self.buzzed.load(Ordering::Relaxed),
+            BuzzMode::Queue => !self.buzz_queue.iter().any(|p| p == player_name),
+        }
+    }
+
     pub async fn add_buzz(&mut self, author: String) -> bool {
+        let accepted = self.accept_buzz(author).await;
+
+        if accepted {
+            self.observe_reaction(Instant::now());
+        }
+
+        accepted
+    }
+
+    /// ##Give the buzz to a player, or queue it
+    ///
+    /// __author__ : name of player who has buzzed
+    async fn accept_buzz(&mut self, author: String) -> bool {
+        if self.buzz_mode == BuzzMode::Queue {
+            return self.queue_buzz(author).await;
+        }
+
         if !self.buzzed.load(Ordering::Relaxed) {
+            info!(parent: &self.span, player = %author, "Buzz given");
             self.buzzed.store(true, Ordering::Relaxed);
             self.buzz_author = Some(author);
             self.send(Messages::CanBuzz { can_buzz: false })
@@ -86,12 +345,166 @@ impl GameInfo {
         false
     }
 
+    /// ##Add a buzz at the end of the buzz queue
+    ///
+    /// The first player of the queue gets the turn to answer, the buzzer stays open for the others
+    ///
+    /// __author__ : name of player who has buzzed
+    async fn queue_buzz(&mut self, author: String) -> bool {
+        if self.buzz_queue.contains(&author) {
+            return false;
+        }
+
+        info!(
+            parent: &self.span,
+            player = %author,
+            position = self.buzz_queue.len(),
+            "Buzz queued"
+        );
+        self.buzz_queue.push(author.clone());
+
+        if !self.buzzed.load(Ordering::Relaxed) {
+            self.buzzed.store(true, Ordering::Relaxed);
+            self.buzz_author = Some(author);
+        }
+
+        self.send(self.buzz_queue_message()).await;
+
+        true
+    }
+
+    /// ##Pass the turn to the next player of the buzz queue
+    ///
+    /// _return_ the player whose turn it is, None if the queue is exhausted
+    pub async fn pass_turn(&mut self) -> Option<String> {
+        let position = self
+            .buzz_author
+            .as_ref()
+            .and_then(|author| self.buzz_queue.iter().position(|p| p == author))?;
+
+        let next = self.buzz_queue.get(position + 1).cloned()?;
+
+        self.buzz_author = Some(next.clone());
+        info!(parent: &self.span, player = %next, "Turn passed");
+
+        self.send(self.buzz_queue_message()).await;
+
+        Some(next)
+    }
+
+    /// ##Buzz queue message, with the player whose turn it is
+    fn buzz_queue_message(&self) -> Messages {
+        Messages::BuzzQueue {
+            queue: self.buzz_queue.clone(),
+            current: self.buzz_author.clone(),
+        }
+    }
+
+    /// ##Collect a buzz received during the buzz window
+    ///
+    /// The buzz is compensated by half the round trip time of the player, at most the buzz window
+    ///
+    /// __author__ : name of player who has buzzed
+    ///
+    /// __rtt__ : round trip time measured on the connection the buzz was received from
+    ///
+    /// _return_ true for the first buzz of the window
+    pub fn collect_buzz(&mut self, author: String, rtt: Duration) -> bool {
+        if self
+            .pending_buzzes
+            .iter()
+            .any(|candidate| candidate.player_name == author)
+        {
+            return false;
+        }
+
+        self.pending_buzzes.push(BuzzCandidate {
+            player_name: author,
+            received_at: Instant::now(),
+            adjustment: (rtt / 2).min(self.buzz_window),
+        });
+
+        self.pending_buzzes.len() == 1
+    }
+
+    /// ##Give the buzz to the earliest collected buzz, once adjusted by the players latency
+    ///
+    /// The collected buzzes and their adjustments are logged for dispute review.
+    /// With queued buzzes, all the collected buzzes are queued in the adjusted order
+    ///
+    /// _return_ the buzz winner, None if no buzz was collected
+    pub async fn resolve_buzz(&mut self) -> Option<String> {
+        let mut candidates = std::mem::take(&mut self.pending_buzzes);
+
+        let first = candidates.iter().map(|c| c.received_at).min()?;
+
+        candidates.sort_by_key(|c| c.pressed_at());
+
+        let winner = candidates.first()?.player_name.clone();
+
+        let question = self
+            .current_question
+            .as_ref()
+            .and_then(|(q, _)| question_number(q));
+
+        for candidate in candidates.iter() {
+            info!(
+                parent: &self.span,
+                question = ?question,
+                player = %candidate.player_name,
+                received_after = ?(candidate.received_at - first),
+                adjustment = ?candidate.adjustment,
+                "Buzz arbitration candidate"
+            );
+        }
+
+        info!(
+            parent: &self.span,
+            question = ?question,
+            player = %winner,
+            candidates = candidates.len(),
+            "Buzz arbitration winner"
+        );
+
+        if !self.accept_buzz(winner.clone()).await {
+            return None;
+        }
+
+        self.observe_reaction(candidates[0].pressed_at());
+
+        // the other buzzes are queued in the adjusted order
+        if self.buzz_mode == BuzzMode::Queue {
+            for candidate in candidates.into_iter().skip(1) {
+                let pressed_at = candidate.pressed_at();
+
+                if self.accept_buzz(candidate.player_name).await {
+                    self.observe_reaction(pressed_at);
+                }
+            }
+        }
+
+        Some(winner)
+    }
+
     pub async fn release_buzz(&mut self) {
         self.buzzed.store(false, Ordering::Relaxed);
         self.buzz_author = None;
+        self.pending_buzzes.clear();
+        self.buzz_queue.clear();
+        self.buzz_opened_at = Some(Instant::now());
+        debug!(parent: &self.span, "Buzzer opened");
         self.send(Messages::CanBuzz { can_buzz: true }).await;
     }
 
+    /// ##Observe the reaction time of a buzz since the buzzer has been opened
+    ///
+    /// __pressed_at__ : time the player has pressed the buzz
+    fn observe_reaction(&self, pressed_at: Instant) {
+        if let Some(opened_at) = self.buzz_opened_at {
+            observe_reaction_time(pressed_at.saturating_duration_since(opened_at));
+        }
+    }
+
     pub async fn send(&self, message: Messages) {
         let senders = self.senders.lock().await;
         let players = senders
@@ -100,25 +513,113 @@ impl GameInfo {
             .map(|s| String::from(s.as_str()))
             .collect::<Vec<String>>();
 
-        senders.clone().iter().for_each(|(_name, tx)| {
-            let s = match message.clone() {
-                Messages::Question { .. } => StateChange::with_question(message.clone()),
-                Messages::PlayerAnswer { .. } => StateChange::with_answer(message.clone()),
-                Messages::Buzz { .. } => StateChange::with_buzz(message.clone()),
-                Messages::PlayerScore { .. } => {
-                    StateChange::with_score(message.clone(), players.clone(), self.min_players)
-                }
-                Messages::CanBuzz { can_buzz } => StateChange::with_can_buzz(can_buzz),
-                Messages::Error { message } => StateChange::with_error(message.clone()),
-                Messages::GameStart => StateChange::start(players.clone(), self.min_players),
-                Messages::None => StateChange::end()
-            };
+        let mut s = self.state_change(message, players);
+        s.id = Some(self.next_event_id());
+
+        // keep the event to replay it to the players reconnecting
+        let mut history = self.history.lock().await;
+        history.push_back(s.clone());
+        while history.len() > self.history_size {
+            history.pop_front();
+        }
 
-            tx.send(s).unwrap();
+        // a player whose connection is lost misses the event until it reconnects
+        senders.values().for_each(|tx| {
+            if tx.send(s.clone()).is_err() {
+                METRICS.send_failures.inc();
+            }
         });
     }
 
-    pub async fn next_question(&mut self) {
+    /// ##Id of the next event of the game
+    fn next_event_id(&self) -> u64 {
+        self.last_event_id.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// ##Check if a player is registered in the game, connected or not
+    ///
+    /// __name__ : the player name
+    pub async fn is_registered(&self, name: &str) -> bool {
+        self.senders.lock().await.contains_key(name)
+    }
+
+    /// ##Resume the events of a player whose connection has been lost
+    ///
+    /// The events following the last one received by the player are replayed from the history,
+    /// the older ones being lost if they are no longer in the history.
+    /// Fails with PlayerAlreadyConnectedError if the previous connection of the player is still open
+    ///
+    /// __name__ : the player name
+    ///
+    /// __last_event_id__ : id of the last event received by the player
+    ///
+    /// __tx__ : event sender of the new connection
+    pub async fn resume_player(
+        &self,
+        name: String,
+        last_event_id: u64,
+        tx: UnboundedSender<StateChange>,
+    ) -> Result<(), CustomError> {
+        let mut senders = self.senders.lock().await;
+
+        // the stream of a connected player can not be taken over
+        if senders.get(&name).is_some_and(|tx| !tx.is_closed()) {
+            return Err(CustomError::PlayerAlreadyConnectedError(name));
+        }
+
+        for s in self.history.lock().await.iter() {
+            if s.id.is_some_and(|id| id > last_event_id) {
+                tx.send(s.clone())?;
+            }
+        }
+
+        senders.insert(name, tx);
+
+        Ok(())
+    }
+
+    /// ##Send a message to a single player
+    ///
+    /// Used for errors and private notifications, the other players are not notified.
+    /// The event is not kept in the history, so it has no id
+    ///
+    /// __player_name__ : name of the player
+    ///
+    /// __message__ : message to send
+    pub async fn send_to(&self, player_name: &str, message: Messages) {
+        let senders = self.senders.lock().await;
+        let players = senders.keys().cloned().collect::<Vec<String>>();
+
+        if let Some(tx) = senders.get(player_name) {
+            if tx.send(self.state_change(message, players)).is_err() {
+                METRICS.send_failures.inc();
+            }
+        }
+    }
+
+    /// ##State change carrying a message
+    ///
+    /// __players__ : names of the connected players
+    fn state_change(&self, message: Messages, players: Vec<String>) -> StateChange {
+        match message.clone() {
+            Messages::Question { .. } => StateChange::with_question(message),
+            Messages::PlayerAnswer { .. } => StateChange::with_answer(message),
+            Messages::Buzz { .. } => StateChange::with_buzz(message),
+            Messages::PlayerScore { .. } => {
+                StateChange::with_score(message, players, self.min_players)
+            }
+            Messages::CanBuzz { can_buzz } => StateChange::with_can_buzz(can_buzz),
+            Messages::BuzzQueue { .. } => StateChange::with_buzz_queue(message),
+            Messages::Error { .. } => StateChange::with_error(message),
+            Messages::GameStart => StateChange::start(players, self.min_players),
+            Messages::None => StateChange::end(),
+        }
+    }
+
+    /// ##Send the next question to the players
+    ///
+    /// _return_ false if there is no more question and the game has ended
+    pub async fn next_question(&mut self) -> bool {
         return if let Some(Messages::Question {
             number,
             label,
@@ -131,18 +632,28 @@ impl GameInfo {
                 number,
                 label: String::from(label.as_str()),
                 points,
-                answers: answers.clone(),
+                answers,
             };
 
+            self.span.record("question", number);
+            info!(parent: &self.span, question = number, points, "New question");
+
             self.release_buzz().await;
 
             self.send(q.clone()).await;
 
-            if let Some(g_answer) = answers.into_iter().filter(|a| a.good).next() {
-                self.load_current_question(q, g_answer.clone());
+            if let Some(g_answer) = good_answer(&q) {
+                self.load_current_question(q, g_answer);
             }
+
+            true
         } else {
+            self.current_question = None;
+            self.ended.store(true, Ordering::Relaxed);
+            info!(parent: &self.span, "Game ended");
             self.send(Messages::None).await;
+
+            false
         };
     }
 
@@ -159,14 +670,34 @@ impl GameInfo {
     }
 }
 
+/// Number of a question message
+fn question_number(question: &Messages) -> Option<i32> {
+    match question {
+        Messages::Question { number, .. } => Some(*number as i32),
+        _ => None,
+    }
+}
+
+/// Good answer of a question message
+fn good_answer(question: &Messages) -> Option<Answer> {
+    match question {
+        Messages::Question { answers, .. } => answers.iter().find(|a| a.good).cloned(),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod game_info_tests {
     use std::collections::HashSet;
     use std::sync::atomic::Ordering;
+    use std::time::Duration;
 
     use rstest::*;
 
+    use crate::config::game::BuzzMode;
     use crate::dto::messages::{Answer, Messages};
+    use crate::dto::states::StateChangeType;
+    use crate::errors::error::CustomError;
     use crate::game_info::GameInfo;
     use crate::StateChange;
 
@@ -228,4 +759,228 @@ mod game_info_tests {
         info.release_buzz().await;
         assert_eq!(false, info.buzzed.load(Ordering::Relaxed));
     }
+
+    #[rstest]
+    async fn snapshot_restore_test() {
+        let question = |number: u8| Messages::Question {
+            number,
+            label: "".to_string(),
+            points: 1,
+            answers: HashSet::from([Answer {
+                number: 0,
+                label: "".to_string(),
+                good: true,
+            }]),
+        };
+
+        let questions = vec![question(2), question(0), question(1)];
+
+        let mut info = GameInfo::new(questions.clone());
+        info.min_players = 1;
+
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        info.add_player("Joe".to_string(), tx).await;
+        info.next_question().await;
+        info.add_buzz("Joe".to_string()).await;
+
+        let state = info.snapshot().await;
+
+        assert!(state.started);
+        assert_eq!(vec!["Joe".to_string()], state.players);
+        assert_eq!(vec![0, 1], state.remaining_questions);
+        assert_eq!(Some(2), state.current_question);
+        assert_eq!(Some("Joe".to_string()), state.buzz_author);
+        assert_eq!(
+            info.last_event_id.load(Ordering::Relaxed) as i64,
+            state.last_event_id
+        );
+
+        let mut restored = GameInfo::restore(questions, state.clone());
+
+        assert!(restored.is_awaited("Joe"));
+        assert!(restored.buzzed.load(Ordering::Relaxed));
+        assert_eq!(state, restored.snapshot().await);
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        restored
+            .reconnect_player("Joe".to_string(), tx)
+            .await
+            .unwrap();
+
+        assert!(!restored.is_awaited("Joe"));
+        assert_eq!(
+            Some(StateChangeType::GameStart),
+            rx.recv().await.map(|s| s.change_type)
+        );
+        assert_eq!(Some(question(2)), rx.recv().await.map(|s| s.message));
+        assert_eq!(
+            Some(Messages::CanBuzz { can_buzz: false }),
+            rx.recv().await.map(|s| s.message)
+        );
+
+        // the event ids go on from the saved one
+        restored.send(Messages::None).await;
+        assert_eq!(
+            Some(state.last_event_id as u64 + 1),
+            rx.recv().await.and_then(|s| s.id)
+        );
+    }
+
+    #[rstest(default_game_info as info)]
+    async fn resolve_buzz_test(info: GameInfo) {
+        let mut info = info;
+        info.buzz_window = Duration::from_millis(100);
+
+        // Anna is far from the server, her round trip time is 60ms
+        let rtt = Duration::from_millis(60);
+
+        assert!(info.collect_buzz("Tom".to_string(), Duration::ZERO));
+        assert!(!info.collect_buzz("Tom".to_string(), Duration::ZERO));
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        assert!(!info.collect_buzz("Anna".to_string(), rtt));
+        assert_eq!(2, info.pending_buzzes.len());
+        assert_eq!(Duration::from_millis(30), info.pending_buzzes[1].adjustment);
+
+        assert_eq!(Some("Anna".to_string()), info.resolve_buzz().await);
+        assert!(info.buzzed.load(Ordering::Relaxed));
+        assert_eq!(Some("Anna".to_string()), info.buzz_author);
+        assert!(info.pending_buzzes.is_empty());
+
+        assert_eq!(None, info.resolve_buzz().await);
+    }
+
+    #[rstest(default_game_info as info)]
+    async fn buzz_queue_test(info: GameInfo) {
+        let mut info = info;
+        info.buzz_mode = BuzzMode::Queue;
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        info.add_player("Tom".to_string(), tx).await;
+
+        assert!(info.add_buzz("Tom".to_string()).await);
+        assert!(info.add_buzz("Tim".to_string()).await);
+        assert!(!info.add_buzz("Tom".to_string()).await);
+
+        assert!(!info.can_buzz("Tim"));
+        assert!(info.can_buzz("Anna"));
+        assert!(info.check_answer_author("Tom".to_string()));
+
+        assert_eq!(
+            Some(Messages::BuzzQueue {
+                queue: vec!["Tom".to_string()],
+                current: Some("Tom".to_string()),
+            }),
+            rx.recv().await.map(|s| s.message)
+        );
+
+        assert_eq!(Some("Tim".to_string()), info.pass_turn().await);
+        assert!(info.check_answer_author("Tim".to_string()));
+        assert_eq!(
+            vec!["Tom".to_string(), "Tim".to_string()],
+            info.snapshot().await.buzz_queue
+        );
+
+        assert_eq!(None, info.pass_turn().await);
+
+        info.release_buzz().await;
+
+        assert!(info.buzz_queue.is_empty());
+        assert!(info.can_buzz("Tom"));
+    }
+
+    #[rstest(default_game_info as info)]
+    async fn send_to_test(info: GameInfo) {
+        let mut info = info;
+
+        let (tom_tx, mut tom_rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        let (tim_tx, mut tim_rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        info.add_player("Tom".to_string(), tom_tx).await;
+        info.add_player("Tim".to_string(), tim_tx).await;
+
+        let error = Messages::error(&CustomError::BuzzTakenError);
+
+        info.send_to("Tim", error.clone()).await;
+        info.send_to("Anna", error.clone()).await;
+
+        let change = tim_rx.try_recv().unwrap();
+        assert_eq!(StateChangeType::Error, change.change_type);
+        assert_eq!(error, change.message);
+        assert_eq!(None, change.id);
+
+        assert!(tom_rx.try_recv().is_err());
+
+        // the private events leave no gap in the ids of the history
+        let last_event_id = info.last_event_id.load(Ordering::Relaxed);
+        info.send(Messages::None).await;
+        assert_eq!(
+            Some(last_event_id + 1),
+            tom_rx.try_recv().ok().and_then(|s| s.id)
+        );
+    }
+
+    #[rstest(default_game_info as info)]
+    async fn event_history_test(info: GameInfo) {
+        let mut info = info;
+        info.history_size = 2;
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        info.add_player("Tom".to_string(), tx).await;
+
+        for author in ["Tom", "Tim", "Anna"] {
+            info.send(Messages::Buzz {
+                author: author.to_string(),
+            })
+            .await;
+        }
+
+        for id in 1..=3 {
+            assert_eq!(Some(id), rx.recv().await.and_then(|s| s.id));
+        }
+
+        let history = info.history.lock().await;
+        assert_eq!(
+            vec![Some(2), Some(3)],
+            history.iter().map(|s| s.id).collect::<Vec<Option<u64>>>()
+        );
+    }
+
+    #[rstest(default_game_info as info)]
+    async fn resume_player_test(info: GameInfo) {
+        let mut info = info;
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        info.add_player("Tom".to_string(), tx).await;
+
+        for author in ["Tom", "Tim", "Anna"] {
+            info.send(Messages::Buzz {
+                author: author.to_string(),
+            })
+            .await;
+        }
+
+        // the stream of a player still connected can not be taken over
+        let (tx, _other_rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        assert!(matches!(
+            info.resume_player("Tom".to_string(), 1, tx).await,
+            Err(CustomError::PlayerAlreadyConnectedError(name)) if name == "Tom"
+        ));
+
+        // the connection is lost after the first event
+        drop(rx);
+        info.send(Messages::CanBuzz { can_buzz: true }).await;
+
+        assert!(info.is_registered("Tom").await);
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        info.resume_player("Tom".to_string(), 1, tx).await.unwrap();
+
+        for id in 2..=4 {
+            assert_eq!(Some(id), rx.recv().await.and_then(|s| s.id));
+        }
+
+        info.send(Messages::CanBuzz { can_buzz: false }).await;
+        assert_eq!(Some(5), rx.recv().await.and_then(|s| s.id));
+    }
 }
@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 
 use std::collections::HashSet;
+use std::path::Path;
 use std::sync::Arc;
 
 use rand::seq::SliceRandom;
@@ -9,13 +10,19 @@ use warp::Filter;
 
 use data::db::*;
 
-use crate::config::app::init_config;
-use crate::data::repositories::PlayerRepository;
+use crate::config::app::{init_config, AppConfig, StorageKind};
+use crate::config::db::DBKind;
+use crate::config::log::init_logging;
+use crate::data::memory::{MemoryGameRepository, MemoryPlayerRepository};
+use crate::data::repositories::{GameRepository, PlayerRepository};
+use crate::data::storage::{GameStorage, PlayerStorage};
 use crate::dto::messages::{Answer, Messages};
+use crate::dto::schemas::write_schemas;
 use crate::dto::states::StateChange;
 use crate::errors::error::CustomError;
 use crate::game_info::GameInfo;
 use crate::services::buzz_services::BuzzService;
+use crate::web::rate_limit::RateLimiter;
 use crate::web::routes::Routes;
 
 mod config;
@@ -23,55 +30,151 @@ mod data;
 mod dto;
 mod errors;
 mod game_info;
+mod metrics;
 mod services;
 mod utils;
 mod web;
 
+// Directory the JSON schemas are written to by default
+const DEFAULT_SCHEMAS_DIR: &str = "./schemas";
+
 #[tokio::main]
 async fn main() -> Result<(), CustomError> {
+    // Write the JSON schemas of the DTOs instead of serving : buzz-game schemas [directory]
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.get(1).map(String::as_str) == Some("schemas") {
+        let dir = args.get(2).map_or(DEFAULT_SCHEMAS_DIR, String::as_str);
+
+        return write_schemas(Path::new(dir)).await;
+    }
+
     // Initialize application config
     let config = init_config().await?;
 
-    // Initialize database
-    let db_pool = create_db_pool(&config)?;
+    // Initialize logs as configured
+    init_logging(&config.log)?;
 
-    let pool = db_pool.clone();
+    match (config.storage, config.db.kind) {
+        (StorageKind::Memory, _) => {
+            serve(
+                &config,
+                BuzzService {
+                    repository: MemoryPlayerRepository::new(),
+                    game_repository: MemoryGameRepository::new(),
+                },
+            )
+            .await
+        }
+        (StorageKind::Database, DBKind::Postgres) => {
+            // Initialize database before serving, waiting for it to be reachable
+            let db_pool = create_db_pool(&config)?;
 
-    tokio::spawn(async move {
-        let connection = get_connection(&pool).await.unwrap();
-        let _ = data::db::init_db(&connection).await.unwrap();
-    });
+            init_db_with_retry(&db_pool, &config.db.retry).await?;
 
-    let game_info = Arc::new(Mutex::new(GameInfo::new(list_of_questions())));
+            metrics::watch_db_pool(db_pool.clone());
 
-    let service = Arc::new(Mutex::new(BuzzService {
-        repository: PlayerRepository::new(db_pool.clone()),
-    }));
+            serve(
+                &config,
+                BuzzService {
+                    repository: PlayerRepository::new(db_pool.clone()),
+                    game_repository: GameRepository::new(db_pool.clone()),
+                },
+            )
+            .await
+        }
+        #[cfg(feature = "sqlite")]
+        (StorageKind::Database, DBKind::Sqlite) => {
+            // Initialize sqlite database
+            let pool = data::sqlite::create_sqlite_pool(&config)?;
+
+            data::sqlite::init_sqlite_db(&pool).await?;
 
-    warp::serve(
-        Routes::add_player(service.clone(), game_info.clone())
-            .or(Routes::register_buzz(service.clone(), game_info.clone()))
-            .or(Routes::register_answer(service.clone(), game_info.clone()))
-            .with(
-                warp::cors()
-                    .allow_any_origin()
-                    .allow_methods(vec!["POST", "GET", "OPTIONS"])
-                    .allow_headers(vec![
-                        "Accept",
-                        "User-Agent",
-                        "Sec-Fetch-Mode",
-                        "Referer",
-                        "Origin",
-                        "Access-Control-Request-Method",
-                        "Access-Control-Request-Headers",
-                        "Access-Control-Allow-Origin",
-                        "Content-type",
-                    ]),
+            serve(
+                &config,
+                BuzzService {
+                    repository: data::sqlite::SqlitePlayerRepository::new(pool.clone()),
+                    game_repository: data::sqlite::SqliteGameRepository::new(pool.clone()),
+                },
             )
-            .recover(crate::web::exception_handlers::handle_error),
-    )
-    .run(([127, 0, 0, 1], 3030))
-    .await;
+            .await
+        }
+        #[cfg(not(feature = "sqlite"))]
+        (StorageKind::Database, DBKind::Sqlite) => Err(CustomError::UnsupportedDBKindError(
+            "sqlite (build with the 'sqlite' feature)".to_string(),
+        )),
+    }
+}
+
+/// Serve the game api
+///
+/// __config__ : application configuration
+///
+/// __service__ : the buzz service backed by the configured storage
+async fn serve<P: PlayerStorage, G: GameStorage>(
+    config: &AppConfig,
+    service: BuzzService<P, G>,
+) -> Result<(), CustomError> {
+    let server = &config.server;
+    let addr = server.socket_addr()?;
+
+    tracing::info!(name = %config.name, %addr, "Serving the game api");
+
+    // Restore the game in progress if the server has been restarted
+    let mut game_info = match service.game_repository.load().await? {
+        None => GameInfo::new(list_of_questions()),
+        Some(state) => GameInfo::restore(list_of_questions(), state),
+    };
+
+    game_info.configure(&config.game);
+
+    let game_info = Arc::new(Mutex::new(game_info));
+
+    service.resume_game(game_info.clone()).await?;
+
+    let service = Arc::new(Mutex::new(service));
+
+    let limiter = RateLimiter::new(config.rate_limit.clone());
+
+    let routes = Routes::versioned(service.clone(), game_info.clone(), limiter)
+        .or(Routes::metrics(game_info.clone()))
+        .or(Routes::health())
+        .or(Routes::ready(service.clone(), game_info.clone()))
+        .with(
+            warp::cors()
+                .allow_any_origin()
+                .allow_methods(vec!["POST", "GET", "OPTIONS"])
+                .allow_headers(vec![
+                    "Accept",
+                    "User-Agent",
+                    "Sec-Fetch-Mode",
+                    "Referer",
+                    "Origin",
+                    "Access-Control-Request-Method",
+                    "Access-Control-Request-Headers",
+                    "Access-Control-Allow-Origin",
+                    "Content-type",
+                ]),
+        )
+        .recover(crate::web::exception_handlers::handle_error)
+        .with(warp::log::custom(metrics::observe_request))
+        .with(warp::trace::request());
+
+    match server.tls()? {
+        Some((cert, key)) => {
+            // read the certificate files first so that a missing file is reported as an error
+            let cert = tokio::fs::read(cert).await?;
+            let key = tokio::fs::read(key).await?;
+
+            warp::serve(routes)
+                .tls()
+                .cert(cert)
+                .key(key)
+                .run(addr)
+                .await
+        }
+        None => warp::serve(routes).run(addr).await,
+    }
 
     Ok(())
 }
@@ -0,0 +1,253 @@
+use std::time::Duration;
+
+use once_cell::sync::{Lazy, OnceCell};
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use warp::log::Info;
+
+use crate::data::db::DBPool;
+use crate::errors::error::CustomError;
+use crate::game_info::GameInfo;
+
+/// ##Metrics of the application, exposed to Prometheus
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+// Database pool watched by the metrics, when the game is stored in postgres
+static DB_POOL: OnceCell<DBPool> = OnceCell::new();
+
+/// ##Application metrics
+///
+/// __connected_players__ : players connected to the game, updated when the metrics are gathered
+///
+/// __active_games__ : games started and not ended, updated when the metrics are gathered
+///
+/// __buzzes__ : buzzes received
+///
+/// __answers__ : answers received, labelled by result (correct or incorrect)
+///
+/// __reaction_time__ : time between the opening of the buzzer and a buzz
+///
+/// __send_failures__ : events which could not be sent to a player
+///
+/// __db_pool_connections__ : database pool connections, labelled by state (in_use, idle or max_open)
+///
+/// __request_duration__ : http requests latency, labelled by route, method and status
+pub struct Metrics {
+    registry: Registry,
+    pub connected_players: IntGauge,
+    pub active_games: IntGauge,
+    pub buzzes: IntCounter,
+    pub answers: IntCounterVec,
+    pub reaction_time: Histogram,
+    pub send_failures: IntCounter,
+    pub db_pool_connections: IntGaugeVec,
+    pub request_duration: HistogramVec,
+}
+
+impl Metrics {
+    /// ###Create the metrics, registered with the "buzz" prefix
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("buzz".to_string()), None).unwrap();
+
+        let metrics = Self {
+            connected_players: IntGauge::new("connected_players", "Players connected to the game")
+                .unwrap(),
+            active_games: IntGauge::new("active_games", "Games started and not ended").unwrap(),
+            buzzes: IntCounter::new("buzzes_total", "Buzzes received").unwrap(),
+            answers: IntCounterVec::new(
+                Opts::new("answers_total", "Answers received by result"),
+                &["result"],
+            )
+            .unwrap(),
+            reaction_time: Histogram::with_opts(
+                HistogramOpts::new(
+                    "reaction_seconds",
+                    "Time between the opening of the buzzer and a buzz",
+                )
+                .buckets(vec![0.25, 0.5, 1.0, 2.0, 3.0, 5.0, 10.0, 20.0, 30.0]),
+            )
+            .unwrap(),
+            send_failures: IntCounter::new(
+                "sse_send_failures_total",
+                "Events which could not be sent to a player",
+            )
+            .unwrap(),
+            db_pool_connections: IntGaugeVec::new(
+                Opts::new("db_pool_connections", "Database pool connections by state"),
+                &["state"],
+            )
+            .unwrap(),
+            request_duration: HistogramVec::new(
+                HistogramOpts::new("http_request_duration_seconds", "Http requests latency"),
+                &["route", "method", "status"],
+            )
+            .unwrap(),
+            registry,
+        };
+
+        metrics.register();
+        metrics
+    }
+
+    fn register(&self) {
+        let registry = &self.registry;
+
+        registry
+            .register(Box::new(self.connected_players.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(self.active_games.clone()))
+            .unwrap();
+        registry.register(Box::new(self.buzzes.clone())).unwrap();
+        registry.register(Box::new(self.answers.clone())).unwrap();
+        registry
+            .register(Box::new(self.reaction_time.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(self.send_failures.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(self.db_pool_connections.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(self.request_duration.clone()))
+            .unwrap();
+    }
+
+    /// ###Count an answer
+    ///
+    /// __good__ : true if the answer is the good one
+    pub fn answer(&self, good: bool) {
+        let result = if good { "correct" } else { "incorrect" };
+
+        self.answers.with_label_values(&[result]).inc();
+    }
+
+    /// ###Render the metrics in the Prometheus text format
+    ///
+    /// The gauges depending on the game and the database pool are updated first
+    ///
+    /// __game_info__ : the game info
+    pub async fn render(&self, game_info: &GameInfo) -> Result<String, CustomError> {
+        self.connected_players
+            .set(game_info.connected_players().await as i64);
+        self.active_games.set(game_info.is_active() as i64);
+
+        if let Some(pool) = DB_POOL.get() {
+            let state = pool.state().await;
+
+            for (label, value) in [
+                ("in_use", state.in_use),
+                ("idle", state.idle),
+                ("max_open", state.max_open),
+            ] {
+                self.db_pool_connections
+                    .with_label_values(&[label])
+                    .set(value as i64);
+            }
+        }
+
+        let mut buffer = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .map_err(|e| CustomError::MetricsError(e.to_string()))?;
+
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+/// ##Watch the usage of the database pool
+///
+/// __pool__ : the database pool of the application
+pub fn watch_db_pool(pool: DBPool) {
+    let _ = DB_POOL.set(pool);
+}
+
+/// ##Observe the latency of a http request
+///
+/// __info__ : request information given by the warp log filter
+pub fn observe_request(info: Info) {
+    METRICS
+        .request_duration
+        .with_label_values(&[
+            crate::web::routes::route_name(info.path()),
+            info.method().as_str(),
+            info.status().as_str(),
+        ])
+        .observe(info.elapsed().as_secs_f64());
+}
+
+/// ##Observe the reaction time of a player
+///
+/// __reaction_time__ : time between the opening of the buzzer and the buzz
+pub fn observe_reaction_time(reaction_time: Duration) {
+    METRICS.reaction_time.observe(reaction_time.as_secs_f64());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use rstest::*;
+
+    use crate::game_info::GameInfo;
+    use crate::metrics::Metrics;
+    use crate::StateChange;
+
+    // the metrics are rendered from their own registry, the global ones being written by the other tests
+    #[fixture]
+    fn metrics() -> Metrics {
+        Metrics::new()
+    }
+
+    #[rstest]
+    async fn render_test(metrics: Metrics) {
+        let game_info = GameInfo::new(vec![]);
+
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        game_info.senders.lock().await.insert("Tom".to_string(), tx);
+        game_info.started.store(true, Ordering::Relaxed);
+
+        metrics.answer(true);
+        metrics.answer(false);
+
+        let text = metrics.render(&game_info).await.unwrap();
+
+        assert!(
+            text.contains(r#"buzz_answers_total{result="correct"} 1"#),
+            "{}",
+            text
+        );
+
+        assert!(text.contains("buzz_connected_players 1"), "{}", text);
+        assert!(text.contains("buzz_active_games 1"), "{}", text);
+        assert!(text.contains(r#"buzz_answers_total{result="incorrect"}"#));
+        assert!(text.contains("buzz_buzzes_total"));
+        assert!(text.contains("buzz_sse_send_failures_total"));
+    }
+
+    #[rstest]
+    async fn render_after_disconnection_and_game_end_test(metrics: Metrics) {
+        let mut game_info = GameInfo::new(vec![]);
+
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        game_info.senders.lock().await.insert("Tom".to_string(), tx);
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        game_info.senders.lock().await.insert("Tim".to_string(), tx);
+        game_info.started.store(true, Ordering::Relaxed);
+
+        // Tim's stream is dropped, then the last question is over
+        drop(rx);
+        assert!(!game_info.next_question().await);
+
+        assert_eq!(1, game_info.connected_players().await);
+        assert!(!game_info.is_active());
+
+        let text = metrics.render(&game_info).await.unwrap();
+
+        assert!(text.contains("buzz_connected_players 1"), "{}", text);
+        assert!(text.contains("buzz_active_games 0"), "{}", text);
+    }
+}
@@ -4,38 +4,33 @@ mod utils;
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
+    use std::sync::atomic::Ordering;
     use std::sync::Arc;
+    use std::time::Duration;
 
+    use async_trait::async_trait;
     use rstest::*;
     use tokio::sync::Mutex;
     use tokio_stream::wrappers::UnboundedReceiverStream;
     use tokio_stream::StreamExt;
 
-    use crate::config::app::init_config;
-    use crate::data::db::create_db_pool;
+    use crate::config::game::BuzzMode;
+    use crate::data::entities::{GameState, Player};
+    use crate::data::memory::{MemoryGameRepository, MemoryPlayerRepository};
+    use crate::data::storage::{GameStorage, PlayerStorage};
     use crate::dto::messages::Messages;
-    use crate::dto::requests::Requests;
+    use crate::dto::requests::{LeaderboardQuery, ListPlayersQuery, Requests};
     use crate::dto::responses::Response;
-    use crate::{
-        clear_db, get_connection, init_db, Answer, BuzzService, GameInfo, PlayerRepository,
-        StateChange,
-    };
+    use crate::{Answer, BuzzService, CustomError, GameInfo, StateChange};
 
-    #[fixture]
-    async fn repo() -> PlayerRepository {
-        let config = init_config().await.unwrap();
-        let pool = create_db_pool(&config).unwrap().clone();
-        let pool_clone = pool.clone();
-
-        tokio::spawn(async move {
-            let connection = get_connection(&pool_clone).await.unwrap();
-            clear_db(&connection).await.unwrap();
-            init_db(&connection).await.unwrap();
-        })
-        .await
-        .unwrap();
+    type MemoryBuzzService = BuzzService<MemoryPlayerRepository, MemoryGameRepository>;
 
-        PlayerRepository::new(pool.clone())
+    #[fixture]
+    fn service() -> MemoryBuzzService {
+        BuzzService {
+            repository: MemoryPlayerRepository::new(),
+            game_repository: MemoryGameRepository::new(),
+        }
     }
 
     #[fixture]
@@ -48,21 +43,16 @@ mod tests {
     #[rstest]
     #[trace]
     async fn add_player_test(
-        #[future]
-        #[notrace]
-        repo: PlayerRepository,
+        #[notrace] mut service: MemoryBuzzService,
         game_info: Arc<Mutex<GameInfo>>,
     ) {
         let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
-        let mut s = BuzzService {
-            repository: repo.await,
-        };
 
         let mut rx = UnboundedReceiverStream::new(rx);
 
         let name = "Tom".to_string();
 
-        let resp = s
+        let resp = service
             .add_player(Requests::AddPlayer { name: name.clone() }, game_info, tx)
             .await;
 
@@ -93,16 +83,139 @@ mod tests {
 
     #[rstest]
     #[trace]
-    async fn register_buzz_test(
-        #[future]
-        #[notrace]
-        repo: PlayerRepository,
+    async fn add_player_saves_game_state_test(
+        #[notrace] mut service: MemoryBuzzService,
         game_info: Arc<Mutex<GameInfo>>,
     ) {
-        let service = BuzzService {
-            repository: repo.await,
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+
+        let name = "Tom".to_string();
+
+        service
+            .add_player(Requests::AddPlayer { name: name.clone() }, game_info, tx)
+            .await
+            .unwrap();
+
+        let state = service.game_repository.load().await.unwrap();
+
+        assert!(state.is_some());
+
+        let state = state.unwrap();
+
+        assert!(state.started);
+        assert_eq!(vec![name], state.players);
+    }
+
+    #[rstest]
+    #[case(true, None, Some(1))]
+    #[case(true, Some(2), Some(2))]
+    #[case(false, None, None)]
+    #[trace]
+    async fn resume_game_test(
+        #[notrace] service: MemoryBuzzService,
+        #[case] started: bool,
+        #[case] current_question: Option<i32>,
+        #[case] expected: Option<u8>,
+    ) {
+        let questions = (1..=2)
+            .map(|number| Messages::Question {
+                number,
+                label: "".to_string(),
+                points: 2,
+                answers: vec![Answer {
+                    number: 1,
+                    label: "".to_string(),
+                    good: true,
+                }]
+                .into_iter()
+                .collect(),
+            })
+            .collect::<Vec<Messages>>();
+
+        // saved when the last player joined, before the first question was sent
+        let state = GameState {
+            started,
+            players: vec!["Tom".to_string()],
+            remaining_questions: vec![1, 2],
+            current_question,
+            buzz_author: None,
+            buzz_queue: vec![],
+            last_event_id: 3,
         };
 
+        let game_info = Arc::new(Mutex::new(GameInfo::restore(questions, state)));
+
+        service.resume_game(game_info.clone()).await.unwrap();
+
+        let number = |question: &Option<(Messages, Answer)>| match question {
+            Some((Messages::Question { number, .. }, _)) => Some(*number),
+            _ => None,
+        };
+
+        assert_eq!(expected, number(&game_info.lock().await.current_question));
+
+        // the question sent is saved, so that a restart does not send it again
+        if started && current_question.is_none() {
+            let state = service.game_repository.load().await.unwrap().unwrap();
+            assert_eq!(Some(1), state.current_question);
+        }
+    }
+
+    #[rstest]
+    #[trace]
+    async fn add_player_restored_in_lobby_test(#[notrace] mut service: MemoryBuzzService) {
+        let mut game_info = GameInfo::new(vec![]);
+        game_info.min_players = 2;
+        let game_info = Arc::new(Mutex::new(game_info));
+
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+
+        let name = "Tom".to_string();
+
+        let resp = service
+            .add_player(Requests::AddPlayer { name: name.clone() }, game_info, tx)
+            .await;
+
+        assert!(matches!(resp, Ok(Response::PlayerAdded(false))));
+
+        let state = service.game_repository.load().await.unwrap().unwrap();
+
+        assert!(!state.started);
+
+        let game_info = Arc::new(Mutex::new(GameInfo::restore(vec![], state)));
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+
+        let mut rx = UnboundedReceiverStream::new(rx);
+
+        let resp = service
+            .add_player(
+                Requests::AddPlayer { name: name.clone() },
+                game_info.clone(),
+                tx,
+            )
+            .await;
+
+        assert!(matches!(resp, Ok(Response::PlayerAdded(false))));
+
+        let game_info = game_info.lock().await;
+
+        assert!(!game_info.is_awaited(&name));
+        assert!(game_info.senders.lock().await.contains_key(&name));
+        assert_eq!(1, game_info.number_of_players.load(Ordering::Relaxed));
+
+        assert!(matches!(
+            rx.next().await.unwrap().message,
+            Messages::PlayerScore { player_name, .. } if player_name == name
+        ));
+    }
+
+    #[rstest]
+    #[trace]
+    async fn register_buzz_test(
+        #[notrace] service: MemoryBuzzService,
+        game_info: Arc<Mutex<GameInfo>>,
+    ) {
         let name = "Tom".to_string();
 
         let resp = service
@@ -111,6 +224,7 @@ mod tests {
                     player_name: name.clone(),
                 },
                 game_info,
+                Duration::ZERO,
             )
             .await;
 
@@ -120,39 +234,227 @@ mod tests {
         }
     }
 
+    #[rstest]
+    #[case(Duration::ZERO)]
+    #[case(Duration::from_millis(50))]
+    #[trace]
+    async fn register_buzz_saves_last_event_id_test(
+        #[notrace] service: MemoryBuzzService,
+        game_info: Arc<Mutex<GameInfo>>,
+        #[case] buzz_window: Duration,
+    ) {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        {
+            let mut game_info = game_info.lock().await;
+            game_info.buzz_window = buzz_window;
+            game_info.add_player("Tom".to_string(), tx).await;
+        }
+
+        service
+            .register_buzz(
+                Requests::RegisterBuzz {
+                    player_name: "Tom".to_string(),
+                },
+                game_info.clone(),
+                Duration::ZERO,
+            )
+            .await
+            .unwrap();
+
+        if !buzz_window.is_zero() {
+            service.resolve_buzz(game_info.clone()).await.unwrap();
+        }
+
+        // the buzz event is the last one sent, and its id is saved
+        let mut last_sent = None;
+        while let Ok(state) = rx.try_recv() {
+            last_sent = Some(state);
+        }
+        let last_sent = last_sent.unwrap();
+
+        let state = service.game_repository.load().await.unwrap().unwrap();
+
+        assert!(matches!(last_sent.message, Messages::Buzz { .. }));
+        assert_eq!(last_sent.id.map(|id| id as i64), Some(state.last_event_id));
+    }
+
+    #[rstest]
+    #[trace]
+    async fn register_buzz_taken_test(
+        #[notrace] service: MemoryBuzzService,
+        game_info: Arc<Mutex<GameInfo>>,
+    ) {
+        for (name, expected_taken) in [("Tom", false), ("Tim", true)] {
+            let resp = service
+                .register_buzz(
+                    Requests::RegisterBuzz {
+                        player_name: name.to_string(),
+                    },
+                    game_info.clone(),
+                    Duration::ZERO,
+                )
+                .await;
+
+            assert_eq!(
+                expected_taken,
+                matches!(resp, Err(CustomError::BuzzTakenError))
+            );
+        }
+    }
+
+    #[rstest]
+    #[trace]
+    async fn register_buzz_taken_error_sent_to_late_player_test(
+        #[notrace] service: MemoryBuzzService,
+        game_info: Arc<Mutex<GameInfo>>,
+    ) {
+        let (tom_tx, mut tom_rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        let (tim_tx, mut tim_rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        {
+            let mut game_info = game_info.lock().await;
+            game_info.min_players = 2;
+            game_info.add_player("Tom".to_string(), tom_tx).await;
+            game_info.add_player("Tim".to_string(), tim_tx).await;
+        }
+
+        for name in ["Tom", "Tim"] {
+            let _ = service
+                .register_buzz(
+                    Requests::RegisterBuzz {
+                        player_name: name.to_string(),
+                    },
+                    game_info.clone(),
+                    Duration::ZERO,
+                )
+                .await;
+        }
+
+        let is_error = |change: &StateChange| matches!(change.message, Messages::Error { .. });
+
+        let mut tim_changes = vec![];
+        while let Ok(change) = tim_rx.try_recv() {
+            tim_changes.push(change);
+        }
+        let mut tom_changes = vec![];
+        while let Ok(change) = tom_rx.try_recv() {
+            tom_changes.push(change);
+        }
+
+        assert!(tim_changes.iter().any(is_error));
+        assert!(!tom_changes.iter().any(is_error));
+    }
+
+    #[rstest]
+    #[trace]
+    async fn register_buzz_window_test(
+        #[notrace] service: MemoryBuzzService,
+        game_info: Arc<Mutex<GameInfo>>,
+    ) {
+        game_info.lock().await.buzz_window = Duration::from_millis(50);
+
+        for name in ["Tom", "Tim"] {
+            let resp = service
+                .register_buzz(
+                    Requests::RegisterBuzz {
+                        player_name: name.to_string(),
+                    },
+                    game_info.clone(),
+                    Duration::ZERO,
+                )
+                .await;
+
+            assert!(matches!(resp, Ok(Response::BuzzRegistered)));
+        }
+
+        // the buzz is given at the end of the window
+        assert_eq!(None, game_info.lock().await.buzz_author);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+
+        assert_eq!(Some("Tom".to_string()), game_info.lock().await.buzz_author);
+
+        let state = service.game_repository.load().await.unwrap().unwrap();
+
+        assert_eq!(Some("Tom".to_string()), state.buzz_author);
+    }
+
+    #[rstest]
+    #[case::measured_on_connection(Duration::from_millis(80), "Tim")]
+    #[case::without_connection(Duration::ZERO, "Tom")]
+    #[trace]
+    async fn register_buzz_latency_test(
+        #[notrace] service: MemoryBuzzService,
+        game_info: Arc<Mutex<GameInfo>>,
+        #[case] tim_rtt: Duration,
+        #[case] expected: &str,
+    ) {
+        {
+            let game_info = game_info.lock().await;
+            game_info
+                .latencies
+                .lock()
+                .await
+                .insert("Tim".to_string(), Duration::from_millis(80));
+        }
+        game_info.lock().await.buzz_window = Duration::from_millis(50);
+
+        // only the latency given with the buzz compensates it, not the one recorded for its name
+        for (name, rtt) in [("Tom", Duration::ZERO), ("Tim", tim_rtt)] {
+            service
+                .register_buzz(
+                    Requests::RegisterBuzz {
+                        player_name: name.to_string(),
+                    },
+                    game_info.clone(),
+                    rtt,
+                )
+                .await
+                .unwrap();
+        }
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+
+        assert_eq!(
+            Some(expected.to_string()),
+            game_info.lock().await.buzz_author
+        );
+    }
+
     #[rstest]
     #[trace]
     async fn register_answer_test(
-        #[future]
-        #[notrace]
-        repo: PlayerRepository,
+        #[notrace] service: MemoryBuzzService,
         game_info: Arc<Mutex<GameInfo>>,
     ) {
         let name = "Tom".to_string();
 
-        let service = BuzzService {
-            repository: repo.await,
-        };
+        service
+            .repository
+            .insert(&Player::with_name(name.clone()))
+            .await
+            .unwrap();
 
         let g = game_info.clone();
 
-        let mut game = g.lock().await;
+        {
+            let mut game = g.lock().await;
 
-        game.current_question = Some((
-            Messages::Question {
-                number: 0,
-                label: "".to_string(),
-                points: 2,
-                answers: HashSet::new(),
-            },
-            Answer {
-                number: 2,
-                label: "".to_string(),
-                good: true,
-            },
-        ));
+            game.current_question = Some((
+                Messages::Question {
+                    number: 0,
+                    label: "".to_string(),
+                    points: 2,
+                    answers: HashSet::new(),
+                },
+                Answer {
+                    number: 2,
+                    label: "".to_string(),
+                    good: true,
+                },
+            ));
 
-        game.add_buzz(name.clone()).await;
+            game.add_buzz(name.clone()).await;
+        }
 
         let resp = service
             .register_answer(
@@ -180,4 +482,242 @@ mod tests {
             _ => assert!(false),
         }
     }
+
+    #[rstest]
+    #[trace]
+    async fn register_answer_errors_test(
+        #[notrace] service: MemoryBuzzService,
+        game_info: Arc<Mutex<GameInfo>>,
+    ) {
+        {
+            let mut game = game_info.lock().await;
+
+            game.current_question = Some((
+                Messages::Question {
+                    number: 1,
+                    label: "".to_string(),
+                    points: 2,
+                    answers: HashSet::new(),
+                },
+                Answer {
+                    number: 2,
+                    label: "".to_string(),
+                    good: true,
+                },
+            ));
+
+            game.add_buzz("Tom".to_string()).await;
+        }
+
+        let answer = |player_name: &str, question_number: u8| Requests::RegisterAnswer {
+            player_name: player_name.to_string(),
+            question_number,
+            answer_number: 2,
+        };
+
+        let resp = service
+            .register_answer(answer("Tim", 1), game_info.clone())
+            .await;
+
+        assert!(matches!(resp, Err(CustomError::NotBuzzAuthorError(name)) if name == "Tim"));
+
+        let resp = service
+            .register_answer(answer("Tom", 0), game_info.clone())
+            .await;
+
+        assert!(matches!(
+            resp,
+            Err(CustomError::WrongQuestionNumberError {
+                expected: 1,
+                found: 0
+            })
+        ));
+    }
+
+    #[rstest]
+    #[trace]
+    async fn resume_player_test(
+        #[notrace] mut service: MemoryBuzzService,
+        game_info: Arc<Mutex<GameInfo>>,
+    ) {
+        game_info.lock().await.min_players = 2;
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        service
+            .add_player(
+                Requests::AddPlayer {
+                    name: "Tom".to_string(),
+                },
+                game_info.clone(),
+                tx,
+            )
+            .await
+            .unwrap();
+        drop(rx);
+
+        // the reconnecting player gets the events following the last one received
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        let resp = service
+            .resume_player(
+                Requests::AddPlayer {
+                    name: "Tom".to_string(),
+                },
+                0,
+                game_info.clone(),
+                tx,
+            )
+            .await;
+
+        assert!(matches!(resp, Ok(Response::PlayerAdded(false))));
+        assert_eq!(Some(1), rx.recv().await.and_then(|s| s.id));
+
+        // an unknown player is added
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+        let resp = service
+            .resume_player(
+                Requests::AddPlayer {
+                    name: "Tim".to_string(),
+                },
+                3,
+                game_info.clone(),
+                tx,
+            )
+            .await;
+
+        assert!(resp.is_ok());
+        assert!(game_info.lock().await.is_registered("Tim").await);
+    }
+
+    /// Game storage failing its readiness check, or never answering
+    #[derive(Clone)]
+    struct UnreachableGameRepository {
+        hangs: bool,
+    }
+
+    #[async_trait]
+    impl GameStorage for UnreachableGameRepository {
+        async fn save(&self, state: &GameState) -> Result<GameState, CustomError> {
+            Ok(state.clone())
+        }
+
+        async fn load(&self) -> Result<Option<GameState>, CustomError> {
+            Ok(None)
+        }
+
+        async fn clear(&self) -> Result<(), CustomError> {
+            Ok(())
+        }
+
+        async fn ready(&self) -> Result<(), CustomError> {
+            if self.hangs {
+                std::future::pending::<()>().await;
+            }
+
+            Err(CustomError::OpenFileError(std::io::Error::from(
+                std::io::ErrorKind::ConnectionRefused,
+            )))
+        }
+    }
+
+    #[rstest]
+    #[case::failing(false)]
+    #[case::hanging(true)]
+    #[trace]
+    async fn ready_unreachable_storage_test(#[case] hangs: bool) {
+        let service = BuzzService {
+            repository: MemoryPlayerRepository::new(),
+            game_repository: UnreachableGameRepository { hangs },
+        };
+
+        let game_info = Arc::new(Mutex::new(GameInfo::new(vec![Messages::None])));
+
+        let started_at = std::time::Instant::now();
+        let resp = service.ready(game_info).await;
+
+        assert!(matches!(resp, Err(CustomError::NotReadyError(_))));
+        assert!(started_at.elapsed() < Duration::from_secs(2));
+    }
+
+    #[rstest]
+    #[trace]
+    async fn next_turn_test(
+        #[notrace] service: MemoryBuzzService,
+        game_info: Arc<Mutex<GameInfo>>,
+    ) {
+        {
+            let mut game = game_info.lock().await;
+            game.buzz_mode = BuzzMode::Queue;
+            game.add_buzz("Tom".to_string()).await;
+            game.add_buzz("Tim".to_string()).await;
+        }
+
+        // the turn passes to Tim after the wrong answer of Tom
+        service.next_turn(game_info.clone(), false).await.unwrap();
+
+        assert_eq!(Some("Tim".to_string()), game_info.lock().await.buzz_author);
+
+        let state = service.game_repository.load().await.unwrap().unwrap();
+
+        assert_eq!(Some("Tim".to_string()), state.buzz_author);
+
+        // the queue is exhausted after the wrong answer of Tim, the game moves on and ends without question
+        service.next_turn(game_info.clone(), false).await.unwrap();
+
+        assert!(service.game_repository.load().await.unwrap().is_none());
+    }
+
+    #[rstest]
+    #[trace]
+    async fn list_players_and_leaderboard_test(#[notrace] service: MemoryBuzzService) {
+        for (name, score) in [("Tom", 2), ("Tim", 4), ("Anna", 1)] {
+            service
+                .repository
+                .insert(&Player::with_name(name.to_string()))
+                .await
+                .unwrap();
+            service
+                .repository
+                .update_score(name.to_string(), score)
+                .await
+                .unwrap();
+        }
+
+        let resp = service
+            .list_players(ListPlayersQuery {
+                page: None,
+                size: Some(1000),
+                name: Some("t".to_string()),
+            })
+            .await;
+
+        match resp {
+            Ok(Response::Players {
+                players,
+                page,
+                size,
+            }) => {
+                assert_eq!(0, page);
+                assert_eq!(100, size);
+                assert_eq!(
+                    vec!["Tim".to_string(), "Tom".to_string()],
+                    players.into_iter().map(|p| p.name).collect::<Vec<String>>()
+                );
+            }
+            _ => panic!("unexpected response {:?}", resp),
+        }
+
+        let resp = service
+            .leaderboard(LeaderboardQuery { limit: Some(2) })
+            .await;
+
+        match resp {
+            Ok(Response::Leaderboard { players }) => {
+                assert_eq!(
+                    vec![4, 2],
+                    players.into_iter().map(|p| p.score).collect::<Vec<u32>>()
+                );
+            }
+            _ => panic!("unexpected response {:?}", resp),
+        }
+    }
 }
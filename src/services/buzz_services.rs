@@ -1,26 +1,41 @@
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::Mutex;
-use warp::http::StatusCode;
+use tracing::{instrument, Instrument};
 
+use crate::config::game::BuzzMode;
 use crate::data::entities::Player;
-use crate::data::repositories::SearchAttributes;
-use crate::dto::requests::Requests;
+use crate::data::repositories::{Page, SearchAttributes};
+use crate::data::storage::{GameStorage, PlayerStorage};
+use crate::dto::requests::{LeaderboardQuery, ListPlayersQuery, Requests};
 use crate::dto::responses::Response;
+use crate::metrics::METRICS;
 use crate::utils::fn_utils::apply_with;
-use crate::{Answer, CustomError, GameInfo, Messages, PlayerRepository, StateChange};
+use crate::{Answer, CustomError, GameInfo, Messages, StateChange};
+
+// Players listing constants
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+const DEFAULT_LEADERBOARD_SIZE: u32 = 10;
+
+// Longest time to wait for the storage when checking the readiness
+const READY_TIMEOUT: Duration = Duration::from_secs(1);
 
 /// ## Buzz Service : Gestionnaire de la logique metier
 ///
 /// __repostory__ : player repository
+///
+/// __game_repository__ : game state repository
 #[derive(Clone)]
-pub struct BuzzService {
-    pub repository: PlayerRepository,
+pub struct BuzzService<P: PlayerStorage, G: GameStorage> {
+    pub repository: P,
+    pub game_repository: G,
 }
 
-impl BuzzService {
+impl<P: PlayerStorage, G: GameStorage> BuzzService<P, G> {
     /// ## Add new player
     ///
     /// __request__ : AddPlayer request
@@ -28,6 +43,7 @@ impl BuzzService {
     /// __game_info__ : the shared game info
     ///
     /// __tx__ : event sender for the player to add
+    #[instrument(skip_all, fields(player = request.player_name()))]
     pub async fn add_player(
         &mut self,
         request: Requests,
@@ -36,10 +52,46 @@ impl BuzzService {
     ) -> Result<Response, CustomError> {
         let mut game_info = game_info.lock().await;
 
+        // if player of a restored game reconnects, before or after the start of the game
+        if let Requests::AddPlayer { name } = &request {
+            if game_info.is_awaited(name) {
+                // send the scores of all players to the reconnecting player
+                for player_name in game_info.players().await {
+                    if let Some(player) = self
+                        .repository
+                        .find_by(SearchAttributes::Name(player_name.clone()))
+                        .await?
+                    {
+                        tx.send(StateChange::with_score(
+                            Messages::PlayerScore {
+                                player_name,
+                                score: player.score,
+                                good_answer: String::default(),
+                                update: false,
+                            },
+                            vec![],
+                            game_info.min_players,
+                        ))?;
+                    }
+                }
+
+                // register back player sender
+                game_info.reconnect_player(name.clone(), tx).await?;
+
+                // return PlayerAdded response
+                return Ok(Response::PlayerAdded(false));
+            }
+        }
+
         // if game has not started yet
         return if !game_info.started.load(Ordering::Relaxed) {
             // if request is as expected
             return if let Requests::AddPlayer { name } = request {
+                // if the maximum number of players is reached
+                if game_info.is_full() {
+                    return Err(CustomError::GameFullError(game_info.max_players));
+                }
+
                 // save new entity into db
                 self.repository
                     .insert(&Player::with_name(name.clone()))
@@ -58,42 +110,117 @@ impl BuzzService {
                         })
                     .await;
 
+                // save game state
+                self.save_game(&game_info).await?;
+
                 // return PlayerAdded response
                 Ok(Response::PlayerAdded(ready))
             } else {
                 // if request type is not as expected
-                crate::error!(
-                    game -> game_info,
-                    message -> format!("Bad request : expected : Requests::AddPlayer , found : {:?}", request),
-                    status -> StatusCode::INTERNAL_SERVER_ERROR.as_u16()
-                )
+                Err(CustomError::BadRequestTypeError {
+                    message: format!(
+                        "Bad request : expected : Requests::AddPlayer , found : {:?}",
+                        request
+                    ),
+                })
             };
         } else {
             // if game is already started
-            crate::error!(
-                game -> game_info,
-                message -> "Game is already started".to_string(),
-                status -> StatusCode::INTERNAL_SERVER_ERROR.as_u16()
-            )
+            Err(CustomError::GameAlreadyStartedError)
         };
     }
 
+    /// ## Add a player reconnecting after a connection loss
+    ///
+    /// A player still registered in the game gets the events missed since the last one it has received,
+    /// the other players are added as usual
+    ///
+    /// __request__ : AddPlayer request
+    ///
+    /// __last_event_id__ : id of the last event received by the player
+    ///
+    /// __game_info__ : the shared game info
+    ///
+    /// __tx__ : event sender for the player to add
+    #[instrument(skip_all, fields(player = request.player_name()))]
+    pub async fn resume_player(
+        &mut self,
+        request: Requests,
+        last_event_id: u64,
+        game_info: Arc<Mutex<GameInfo>>,
+        tx: UnboundedSender<StateChange>,
+    ) -> Result<Response, CustomError> {
+        if let Requests::AddPlayer { name } = &request {
+            let game_info = game_info.lock().await;
+
+            if game_info.is_registered(name).await {
+                game_info
+                    .resume_player(name.clone(), last_event_id, tx)
+                    .await?;
+
+                return Ok(Response::PlayerAdded(false));
+            }
+        }
+
+        self.add_player(request, game_info, tx).await
+    }
+
     /// ## Register Buzz
     ///
     /// __request__ : RegisterBuzz request
     ///
     /// __game_info__ : the shared game_info
+    ///
+    /// __rtt__ : round trip time measured on the websocket of the player who sent the buzz,
+    /// zero for a buzz without latency compensation
+    #[instrument(skip_all, fields(player = request.player_name()))]
     pub async fn register_buzz(
         &self,
         request: Requests,
         game_info: Arc<Mutex<GameInfo>>,
+        rtt: Duration,
     ) -> Result<Response, CustomError> {
+        let shared_game_info = game_info.clone();
         let mut game_info = game_info.lock().await;
 
-        // if buzz is released
-        return if !game_info.buzzed.load(Ordering::Relaxed) {
+        let buzz_author = match &request {
+            Requests::RegisterBuzz { player_name } => Some(player_name.clone()),
+            _ => None,
+        };
+
+        if buzz_author.is_some() {
+            METRICS.buzzes.inc();
+        }
+
+        let can_buzz = buzz_author
+            .as_ref()
+            .is_none_or(|player_name| game_info.can_buzz(player_name));
+
+        // if buzz is released, or the player can join the buzz queue
+        return if can_buzz {
             // if request type is as expected
             return if let Requests::RegisterBuzz { player_name } = request {
+                // collect the first buzzes during the buzz window, the buzz is given at the end of the window
+                if !game_info.buzz_window.is_zero() && game_info.buzz_author.is_none() {
+                    if game_info.collect_buzz(player_name, rtt) {
+                        let service = self.clone();
+                        let window = game_info.buzz_window;
+
+                        tokio::spawn(
+                            async move {
+                                tokio::time::sleep(window).await;
+
+                                if let Err(e) = service.resolve_buzz(shared_game_info).await {
+                                    tracing::error!(error = %e, "Buzz resolution failed");
+                                }
+                            }
+                            .in_current_span(),
+                        );
+                    }
+
+                    return Ok(Response::BuzzRegistered);
+                }
+
                 // press buzz
                 game_info.add_buzz(player_name.clone()).await;
 
@@ -104,34 +231,63 @@ impl BuzzService {
                         })
                     .await;
 
+                // save game state once sent, so that the saved event id is the last one sent
+                self.save_game(&game_info).await?;
+
                 // return BuzzRegistered response
                 Ok(Response::BuzzRegistered)
             } else {
                 // if request type is not as expected
-                crate::error!(
-                    game -> game_info,
-                    message -> format!(
-                            "Bad request : expected : Requests::RegisterBuzz , found : {:?}",
-                            request
-                        ),
-                    status -> StatusCode::INTERNAL_SERVER_ERROR.as_u16()
-                )
+                Err(CustomError::BadRequestTypeError {
+                    message: format!(
+                        "Bad request : expected : Requests::RegisterBuzz , found : {:?}",
+                        request
+                    ),
+                })
             };
         } else {
-            // if buzz is already pressed
+            let player_name = buzz_author.unwrap_or_default();
+
+            let error = match game_info.buzz_mode {
+                BuzzMode::Single => CustomError::BuzzTakenError,
+                BuzzMode::Queue => CustomError::PlayerAlreadyBuzzedError(player_name.clone()),
+            };
+
+            // if buzz is already pressed, only the late player is notified
             crate::error!(
                 game -> game_info,
-                message -> "Someone else has already buzzed".to_string(),
-                status -> StatusCode::INTERNAL_SERVER_ERROR.as_u16()
+                player -> player_name,
+                error -> error
             )
         };
     }
 
+    /// ## Resolve the buzz at the end of the buzz window
+    ///
+    /// The earliest collected buzz, once adjusted by the player latency, wins
+    ///
+    /// __game_info__ : the shared game_info
+    #[instrument(skip_all)]
+    pub async fn resolve_buzz(&self, game_info: Arc<Mutex<GameInfo>>) -> Result<(), CustomError> {
+        let mut game_info = game_info.lock().await;
+
+        if let Some(author) = game_info.resolve_buzz().await {
+            // send buzz message
+            game_info.send(Messages::Buzz { author }).await;
+
+            // save game state once sent, so that the saved event id is the last one sent
+            self.save_game(&game_info).await?;
+        }
+
+        Ok(())
+    }
+
     /// ## Register answer
     ///
     /// __request__ : RegisterAnswer request
     ///
     /// __game_info__ : the shared game_info
+    #[instrument(skip_all, fields(player = request.player_name()))]
     pub async fn register_answer(
         &self,
         request: Requests,
@@ -161,6 +317,8 @@ impl BuzzService {
                             apply_with(
                                 || number == answer_number,
                                 |good| async move {
+                                    METRICS.answer(good);
+
                                     // send StateChange WithAnswer event
                                     game_info
                                         .send(Messages::PlayerAnswer {
@@ -173,24 +331,25 @@ impl BuzzService {
                                             })
                                         .await;
 
+                                    // save the id of the event sent
+                                    self.save_game(&game_info).await?;
+
                                     // if the given answer is the good one
-                                    // update player score and load the resulted player
-                                    match self
-                                        .repository
-                                        .find_by(SearchAttributes::Name(player_name.clone()))
-                                        .await
-                                    {
-                                        Ok(Some(mut p)) => {
-                                            if good {
-                                                p = self
-                                                    .repository
-                                                    .update_score(
-                                                        player_name.clone(),
-                                                        p.score + points.clone(),
-                                                    )
-                                                    .await?;
-                                            }
+                                    // add the question points to the player score in a single update,
+                                    // otherwise load the player to return its unchanged score
+                                    let player = if good {
+                                        self.repository
+                                            .add_score(player_name.clone(), points)
+                                            .await
+                                            .map(Some)
+                                    } else {
+                                        self.repository
+                                            .find_by(SearchAttributes::Name(player_name.clone()))
+                                            .await
+                                    };
 
+                                    match player {
+                                        Ok(Some(p)) => {
                                             // return Aswer regsiteerd response
                                             Ok(Response::ScoreUpdated(Messages::PlayerScore {
                                                 player_name,
@@ -202,15 +361,15 @@ impl BuzzService {
                                         Err(e) => {
                                             crate::error!(
                                                 game -> game_info,
-                                                message -> e.to_string(),
-                                                status -> StatusCode::INTERNAL_SERVER_ERROR.as_u16()
+                                                player -> player_name,
+                                                error -> e
                                             )
                                         }
                                         _ => {
                                             crate::error!(
                                                 game -> game_info,
-                                                message -> "Unexpected error occured".to_string(),
-                                                status -> StatusCode::INTERNAL_SERVER_ERROR.as_u16()
+                                                player -> player_name.clone(),
+                                                error -> CustomError::PlayerNotFoundWithNameError(player_name)
                                             )
                                         }
                                     }
@@ -220,11 +379,11 @@ impl BuzzService {
                         } else {
                             crate::error!(
                                 game -> game_info,
-                                message -> format!(
-                                    "Bad question number! Expected :{} ; found {}",
-                                    q_num, question_number
-                                ),
-                                status -> StatusCode::BAD_REQUEST.as_u16()
+                                player -> player_name,
+                                error -> CustomError::WrongQuestionNumberError {
+                                    expected: q_num,
+                                    found: question_number,
+                                }
                             )
                         };
                     }
@@ -232,8 +391,8 @@ impl BuzzService {
                         // if current question stored in game info is not as pexpected
                         crate::error!(
                             game -> game_info,
-                            message -> format!("Inappropriate current question registered"),
-                            status -> StatusCode::BAD_REQUEST.as_u16()
+                            player -> player_name,
+                            error -> CustomError::NoCurrentQuestionError
                         )
                     }
                 };
@@ -241,20 +400,154 @@ impl BuzzService {
                 // if the buzz and answer authors are not the same
                 crate::error!(
                     game -> game_info,
-                    message -> "Buzz author is different from answer author".to_string(),
-                    status -> StatusCode::INTERNAL_SERVER_ERROR.as_u16()
+                    player -> player_name.clone(),
+                    error -> CustomError::NotBuzzAuthorError(player_name)
                 )
             };
         } else {
             //  if request type is not as expected
-            crate::error!(
-                game -> game_info,
-                message -> format!(
+            Err(CustomError::BadRequestTypeError {
+                message: format!(
                     "Bad request : expected : Requests::RegisterAnswer , found : {:?}",
                     request
                 ),
-                status -> StatusCode::INTERNAL_SERVER_ERROR.as_u16()
-            )
+            })
+        };
+    }
+
+    /// ## Send next question
+    ///
+    /// Game state is saved while questions remain, and cleared once the game has ended
+    ///
+    /// __game_info__ : the shared game_info
+    #[instrument(skip_all)]
+    pub async fn next_question(&self, game_info: Arc<Mutex<GameInfo>>) -> Result<(), CustomError> {
+        let mut game_info = game_info.lock().await;
+
+        if game_info.next_question().await {
+            self.save_game(&game_info).await
+        } else {
+            self.game_repository.clear().await
+        }
+    }
+
+    /// ## Resume a restored game
+    ///
+    /// A game saved between its start and its first question has no current question,
+    /// the next one is sent so that the game does not stay stuck
+    ///
+    /// __game_info__ : the shared game_info, restored from the saved state
+    #[instrument(skip_all)]
+    pub async fn resume_game(&self, game_info: Arc<Mutex<GameInfo>>) -> Result<(), CustomError> {
+        let stuck = {
+            let game_info = game_info.lock().await;
+            game_info.is_active() && game_info.current_question.is_none()
+        };
+
+        if stuck {
+            self.next_question(game_info).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// ## Pass to the next turn after an answer
+    ///
+    /// After a wrong answer, the turn passes to the next player of the buzz queue if any,
+    /// otherwise the next question is sent
+    ///
+    /// __game_info__ : the shared game_info
+    ///
+    /// __good_answer__ : true if the answer was the good one
+    #[instrument(skip(self, game_info))]
+    pub async fn next_turn(
+        &self,
+        game_info: Arc<Mutex<GameInfo>>,
+        good_answer: bool,
+    ) -> Result<(), CustomError> {
+        if !good_answer {
+            let mut game_info = game_info.lock().await;
+
+            if game_info.pass_turn().await.is_some() {
+                return self.save_game(&game_info).await;
+            }
+        }
+
+        self.next_question(game_info).await
+    }
+
+    /// ## List players
+    ///
+    /// __query__ : requested page and optional part of the players name
+    #[instrument(skip_all)]
+    pub async fn list_players(&self, query: ListPlayersQuery) -> Result<Response, CustomError> {
+        let page = Page {
+            number: query.page.unwrap_or(0),
+            size: query.size.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE),
         };
+
+        let players = self
+            .repository
+            .list(query.name.map(SearchAttributes::NameLike), page)
+            .await?;
+
+        Ok(Response::Players {
+            players,
+            page: page.number,
+            size: page.size,
+        })
+    }
+
+    /// ## Leaderboard
+    ///
+    /// __query__ : requested number of players
+    #[instrument(skip_all)]
+    pub async fn leaderboard(&self, query: LeaderboardQuery) -> Result<Response, CustomError> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_LEADERBOARD_SIZE)
+            .min(MAX_PAGE_SIZE);
+
+        let players = self.repository.leaderboard(limit).await?;
+
+        Ok(Response::Leaderboard { players })
+    }
+
+    /// ## Check that the game can be played
+    ///
+    /// The question bank must be loaded, and the storage reachable with its schema initialized.
+    /// A storage failing or not answering in time makes the game not ready
+    ///
+    /// __game_info__ : the shared game_info
+    #[instrument(skip_all)]
+    pub async fn ready(&self, game_info: Arc<Mutex<GameInfo>>) -> Result<Response, CustomError> {
+        if game_info.lock().await.question_bank_size == 0 {
+            return Err(CustomError::NotReadyError(
+                "question bank is not loaded".to_string(),
+            ));
+        }
+
+        match tokio::time::timeout(READY_TIMEOUT, self.game_repository.ready()).await {
+            Ok(Ok(())) => Ok(Response::Ready),
+            Ok(Err(e @ CustomError::NotReadyError(_))) => Err(e),
+            Ok(Err(e)) => Err(CustomError::NotReadyError(format!(
+                "storage is not reachable: {}",
+                e
+            ))),
+            Err(_) => Err(CustomError::NotReadyError(format!(
+                "storage did not answer within {:?}",
+                READY_TIMEOUT
+            ))),
+        }
+    }
+
+    /// ## Save game state
+    ///
+    /// __game_info__ : the game info to save
+    async fn save_game(&self, game_info: &GameInfo) -> Result<(), CustomError> {
+        self.game_repository
+            .save(&game_info.snapshot().await)
+            .await?;
+        Ok(())
     }
 }
@@ -1,11 +1,16 @@
+/// Send an error to the player who caused it, and return it
 #[macro_export]
 macro_rules! error {
-    (game -> $game: ident, message -> $msg: expr, status -> $status_code: expr) => {{
-        $game.send(Messages::Error { message: $msg }).await;
+    (game -> $game: ident, player -> $player: expr, error -> $error: expr) => {{
+        let player: String = $player;
+        let error: CustomError = $error;
 
-        Ok(Response::Error {
-            message: $msg,
-            code: $status_code,
-        })
+        tracing::warn!(player = %player, code = ?error.code(), "{}", error);
+
+        $game
+            .send_to(&player, Messages::error(&error))
+            .await;
+
+        Err(error)
     }};
 }
@@ -1,4 +1,700 @@
 pub(crate) mod exception_handlers;
 pub(crate) mod handlers;
+pub(crate) mod rate_limit;
 pub(crate) mod routes;
 mod utils;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use rstest::*;
+    use tokio::sync::Mutex;
+
+    use crate::config::rate_limit::{Limit, RateLimitConfig};
+    use crate::data::memory::{MemoryGameRepository, MemoryPlayerRepository};
+    use crate::dto::messages::Messages;
+    use crate::dto::schemas::openapi_schemas;
+    use crate::dto::states::{StateChange, StateChangeType};
+    use crate::web::exception_handlers::{handle_error, status_of};
+    use crate::web::handlers::{BuzzHandlers, PendingPings};
+    use crate::web::rate_limit::{retry_after_seconds, RateLimiter};
+    use crate::web::routes::{route_name, Routes};
+    use crate::{BuzzService, CustomError, GameInfo};
+    use warp::http::StatusCode;
+    use warp::Filter;
+
+    type SharedService = Arc<Mutex<BuzzService<MemoryPlayerRepository, MemoryGameRepository>>>;
+
+    #[fixture]
+    fn service() -> SharedService {
+        Arc::new(Mutex::new(BuzzService {
+            repository: MemoryPlayerRepository::new(),
+            game_repository: MemoryGameRepository::new(),
+        }))
+    }
+
+    #[fixture(questions = vec![])]
+    fn game_info(questions: Vec<Messages>) -> Arc<Mutex<GameInfo>> {
+        Arc::new(Mutex::new(GameInfo::new(questions)))
+    }
+
+    #[rstest]
+    async fn websocket_test(service: SharedService, game_info: Arc<Mutex<GameInfo>>) {
+        let mut client = warp::test::ws()
+            .path("/game/ws?player=Tom")
+            .handshake(Routes::websocket(
+                service,
+                game_info,
+                RateLimiter::default(),
+            ))
+            .await
+            .unwrap();
+
+        // initial score sent downstream
+        let event = client.recv().await.unwrap();
+        assert!(event.to_str().unwrap().contains("NEW_PLAYER_SCORE"));
+
+        // buzz sent upstream
+        client
+            .send_text(r#"{"type":"BUZZ","playerName":"Tom"}"#)
+            .await;
+
+        let resp = client.recv().await.unwrap();
+        assert_eq!(r#"{"type":"BUZZ_REGISTERED"}"#, resp.to_str().unwrap());
+
+        let event = client.recv().await.unwrap();
+        assert!(event.to_str().unwrap().contains("CAN_BUZZ"));
+
+        let event = client.recv().await.unwrap();
+        assert!(event.to_str().unwrap().contains("NEW_BUZZ"));
+
+        // invalid request
+        client.send_text("not a request").await;
+
+        let resp = client.recv().await.unwrap();
+        assert!(resp.to_str().unwrap().contains(r#""code":400"#));
+        assert!(resp.to_str().unwrap().contains(r#""error":"INVALID_BODY""#));
+
+        // request of another player
+        client
+            .send_text(r#"{"type":"BUZZ","playerName":"Tim"}"#)
+            .await;
+
+        let resp = client.recv().await.unwrap();
+        assert!(resp.to_str().unwrap().contains(r#""code":403"#));
+        assert!(resp
+            .to_str()
+            .unwrap()
+            .contains(r#""error":"PLAYER_MISMATCH""#));
+
+        // request without type, warned before the response
+        client.send_text(r#"{"playerName":"Tom"}"#).await;
+
+        let warning = client.recv().await.unwrap();
+        assert!(warning
+            .to_str()
+            .unwrap()
+            .contains(r#""type":"WARNING","warning":"UNTAGGED_REQUEST""#));
+
+        let resp = client.recv().await.unwrap();
+        assert!(resp.to_str().unwrap().contains(r#""error":"BUZZ_TAKEN""#));
+    }
+
+    #[rstest]
+    async fn websocket_disconnection_test(service: SharedService, game_info: Arc<Mutex<GameInfo>>) {
+        let mut client = warp::test::ws()
+            .path("/game/ws?player=Tom")
+            .handshake(Routes::websocket(
+                service,
+                game_info.clone(),
+                RateLimiter::default(),
+            ))
+            .await
+            .unwrap();
+
+        client.recv().await.unwrap();
+        assert_eq!(1, game_info.lock().await.connected_players().await);
+
+        let latencies = game_info.lock().await.latencies.clone();
+        latencies
+            .lock()
+            .await
+            .insert("Tom".to_string(), Duration::from_millis(40));
+
+        drop(client);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // the player stays in the game, but is no more connected
+        assert_eq!(1, game_info.lock().await.players().await.len());
+        assert_eq!(0, game_info.lock().await.connected_players().await);
+        assert!(!latencies.lock().await.contains_key("Tom"));
+    }
+
+    #[rstest]
+    async fn websocket_reconnection_test(service: SharedService, game_info: Arc<Mutex<GameInfo>>) {
+        let connect = || {
+            warp::test::ws()
+                .path("/game/ws?player=Tom")
+                .handshake(Routes::websocket(
+                    service.clone(),
+                    game_info.clone(),
+                    RateLimiter::default(),
+                ))
+        };
+
+        let mut client = connect().await.unwrap();
+        client.recv().await.unwrap();
+
+        // the stream of a connected player can not be taken over
+        assert!(connect().await.is_err());
+
+        drop(client);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // the player gets back its events on a new websocket
+        let mut client = connect().await.unwrap();
+
+        let event = client.recv().await.unwrap();
+        assert!(event.to_str().unwrap().contains("NEW_PLAYER_SCORE"));
+        assert_eq!(1, game_info.lock().await.players().await.len());
+        assert_eq!(1, game_info.lock().await.connected_players().await);
+
+        client
+            .send_text(r#"{"type":"BUZZ","playerName":"Tom"}"#)
+            .await;
+
+        let resp = client.recv().await.unwrap();
+        assert_eq!(r#"{"type":"BUZZ_REGISTERED"}"#, resp.to_str().unwrap());
+    }
+
+    #[rstest]
+    fn pending_pings_test() {
+        let mut pings = PendingPings::default();
+        let sent_at = Instant::now();
+
+        let first = pings.ping(sent_at);
+        let second = pings.ping(sent_at + Duration::from_millis(10));
+
+        // a forged payload or a ping never sent are ignored
+        assert_eq!(None, pings.pong(&0u64.to_be_bytes(), sent_at));
+        assert_eq!(None, pings.pong(&[1, 2], sent_at));
+        assert_eq!(None, pings.pong(&99u64.to_be_bytes(), sent_at));
+
+        // the round trip time is measured from the sending time of the ping
+        assert_eq!(
+            Some(Duration::from_millis(30)),
+            pings.pong(&second, sent_at + Duration::from_millis(40))
+        );
+
+        // a ping is answered once, the older ones are no longer awaited
+        assert_eq!(
+            None,
+            pings.pong(&second, sent_at + Duration::from_millis(50))
+        );
+        assert_eq!(
+            None,
+            pings.pong(&first, sent_at + Duration::from_millis(50))
+        );
+    }
+
+    #[rstest]
+    fn pending_pings_lost_test() {
+        let mut pings = PendingPings::default();
+        let sent_at = Instant::now();
+
+        let first = pings.ping(sent_at);
+        for _ in 0..4 {
+            pings.ping(sent_at);
+        }
+
+        assert_eq!(None, pings.pong(&first, sent_at));
+    }
+
+    #[rstest]
+    async fn rate_limited_websocket_test(service: SharedService, game_info: Arc<Mutex<GameInfo>>) {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            burst: 1,
+            refill_interval_ms: 60_000,
+            ..RateLimitConfig::default()
+        });
+
+        let mut client = warp::test::ws()
+            .path("/game/ws?player=Tom")
+            .handshake(Routes::websocket(service, game_info, limiter))
+            .await
+            .unwrap();
+
+        let event = client.recv().await.unwrap();
+        assert!(event.to_str().unwrap().contains("NEW_PLAYER_SCORE"));
+
+        // the first request consumes the only token of the player
+        client.send_text("not a request").await;
+
+        let resp = client.recv().await.unwrap();
+        assert!(resp.to_str().unwrap().contains(r#""error":"INVALID_BODY""#));
+
+        client
+            .send_text(r#"{"type":"BUZZ","playerName":"Tom"}"#)
+            .await;
+
+        let resp = client.recv().await.unwrap();
+        assert!(resp.to_str().unwrap().contains(r#""code":429"#));
+        assert!(resp
+            .to_str()
+            .unwrap()
+            .contains(r#""error":"TOO_MANY_REQUESTS""#));
+    }
+
+    #[rstest]
+    #[case(CustomError::GameAlreadyStartedError, StatusCode::CONFLICT)]
+    #[case(CustomError::GameFullError(6), StatusCode::CONFLICT)]
+    #[case(CustomError::BuzzTakenError, StatusCode::CONFLICT)]
+    #[case(CustomError::NotBuzzAuthorError("Tom".to_string()), StatusCode::FORBIDDEN)]
+    #[case(
+        CustomError::PlayerMismatchError { expected: "Tom".to_string(), found: "Tim".to_string() },
+        StatusCode::FORBIDDEN
+    )]
+    #[case(
+        CustomError::WrongQuestionNumberError { expected: 1, found: 0 },
+        StatusCode::BAD_REQUEST
+    )]
+    #[case(
+        CustomError::PlayerNotFoundWithNameError("Tom".to_string()),
+        StatusCode::NOT_FOUND
+    )]
+    fn status_of_test(#[case] error: CustomError, #[case] expected: StatusCode) {
+        assert_eq!(expected, status_of(&error));
+    }
+
+    #[rstest]
+    #[case(
+        "POST",
+        "/game/buzz",
+        r#"{"playerName":1}"#,
+        StatusCode::BAD_REQUEST,
+        "playerName"
+    )]
+    #[case(
+        "POST",
+        "/game/answer",
+        r#"{"playerName":"Tom","questionNumber":0}"#,
+        StatusCode::BAD_REQUEST,
+        "answerNumber"
+    )]
+    #[case(
+        "POST",
+        "/game/buzz",
+        r#"{"type":"ANSWER","playerName":"Tom"}"#,
+        StatusCode::BAD_REQUEST,
+        "BAD_REQUEST"
+    )]
+    #[case(
+        "POST",
+        "/game/buzz",
+        "not json",
+        StatusCode::BAD_REQUEST,
+        "INVALID_BODY"
+    )]
+    #[case("GET", "/game", "", StatusCode::BAD_REQUEST, "INVALID_QUERY")]
+    #[case(
+        "GET",
+        "/game/players?page=first",
+        "",
+        StatusCode::BAD_REQUEST,
+        "INVALID_QUERY"
+    )]
+    #[case("GET", "/game/unknown", "", StatusCode::NOT_FOUND, "NOT_FOUND")]
+    #[case(
+        "GET",
+        "/game/buzz",
+        "",
+        StatusCode::METHOD_NOT_ALLOWED,
+        "METHOD_NOT_ALLOWED"
+    )]
+    async fn rejection_test(
+        #[case] method: &str,
+        #[case] path: &str,
+        #[case] body: &str,
+        #[case] expected_status: StatusCode,
+        #[case] expected_fragment: &str,
+        service: SharedService,
+        game_info: Arc<Mutex<GameInfo>>,
+    ) {
+        let routes =
+            Routes::versioned(service, game_info, RateLimiter::default()).recover(handle_error);
+
+        let resp = warp::test::request()
+            .method(method)
+            .path(path)
+            .body(body)
+            .reply(&routes)
+            .await;
+
+        let json = String::from_utf8(resp.body().to_vec()).unwrap();
+
+        assert_eq!(expected_status, resp.status());
+        assert!(json.contains(r#""type":"ERROR""#));
+        assert!(json.contains(expected_fragment), "{}", json);
+    }
+
+    #[rstest]
+    #[case("/api/v1/game/buzz")]
+    #[case("/game/buzz")]
+    async fn versioned_routes_test(
+        #[case] path: &str,
+        service: SharedService,
+        game_info: Arc<Mutex<GameInfo>>,
+    ) {
+        let resp = warp::test::request()
+            .method("POST")
+            .path(path)
+            .body(r#"{"type":"BUZZ","playerName":"Tom"}"#)
+            .reply(&Routes::versioned(
+                service,
+                game_info,
+                RateLimiter::default(),
+            ))
+            .await;
+
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(r#"{"type":"BUZZ_REGISTERED"}"#, resp.body());
+    }
+
+    #[rstest]
+    #[case(r#"{"type":"BUZZ","playerName":"Tom"}"#, None)]
+    #[case(r#"{"playerName":"Tom"}"#, Some("true"))]
+    async fn deprecated_request_test(
+        #[case] body: &str,
+        #[case] expected: Option<&str>,
+        service: SharedService,
+        game_info: Arc<Mutex<GameInfo>>,
+    ) {
+        let resp = warp::test::request()
+            .method("POST")
+            .path("/api/v1/game/buzz")
+            .body(body)
+            .reply(&Routes::versioned(
+                service,
+                game_info,
+                RateLimiter::default(),
+            ))
+            .await;
+
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(
+            expected,
+            resp.headers()
+                .get("deprecation")
+                .map(|h| h.to_str().unwrap())
+        );
+        assert_eq!(expected.is_some(), resp.headers().contains_key("warning"));
+    }
+
+    #[rstest]
+    async fn openapi_test(service: SharedService, game_info: Arc<Mutex<GameInfo>>) {
+        let resp = warp::test::request()
+            .path("/api/v1/openapi.json")
+            .reply(&Routes::versioned(
+                service,
+                game_info,
+                RateLimiter::default(),
+            ))
+            .await;
+
+        assert_eq!(StatusCode::OK, resp.status());
+
+        let doc: serde_json::Value = serde_json::from_slice(resp.body()).unwrap();
+
+        for path in [
+            "/game",
+            "/game/ws",
+            "/game/buzz",
+            "/game/answer",
+            "/game/players",
+            "/game/leaderboard",
+        ] {
+            assert!(doc["paths"][path].is_object(), "{}", path);
+        }
+
+        // the documented state change types are the serialized ones
+        let documented = &doc["components"]["schemas"]["StateChangeType"]["enum"];
+        for change_type in [
+            StateChangeType::GameStart,
+            StateChangeType::GameEnd,
+            StateChangeType::CanBuzz,
+            StateChangeType::NewPlayerScore,
+            StateChangeType::NewQuestion,
+            StateChangeType::NewBuzz,
+            StateChangeType::BuzzQueue,
+            StateChangeType::NewAnswer,
+            StateChangeType::Error,
+        ] {
+            let value = serde_json::to_value(&change_type).unwrap();
+            assert!(documented.as_array().unwrap().contains(&value));
+        }
+
+        // the component schemas are the ones generated from the DTOs
+        assert_eq!(
+            serde_json::to_value(openapi_schemas()).unwrap(),
+            doc["components"]["schemas"]
+        );
+
+        // every reference of the document resolves
+        let mut refs = vec![];
+        collect_refs(&doc, &mut refs);
+        assert!(refs.iter().any(|r| r.starts_with("#/components/schemas/")));
+
+        for reference in refs {
+            let pointer = reference.trim_start_matches('#');
+            assert!(doc.pointer(pointer).is_some(), "{}", reference);
+        }
+    }
+
+    // References of a json document, recursively
+    fn collect_refs(value: &serde_json::Value, refs: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                if let Some(serde_json::Value::String(reference)) = map.get("$ref") {
+                    refs.push(reference.clone());
+                }
+                map.values().for_each(|value| collect_refs(value, refs));
+            }
+            serde_json::Value::Array(values) => {
+                values.iter().for_each(|value| collect_refs(value, refs))
+            }
+            _ => {}
+        }
+    }
+
+    #[rstest]
+    #[case("/schemas/StateChange.json", StatusCode::OK)]
+    #[case("/schemas/Messages.json", StatusCode::OK)]
+    #[case("/schemas/Unknown.json", StatusCode::NOT_FOUND)]
+    async fn schemas_test(#[case] path: &str, #[case] expected_status: StatusCode) {
+        let resp = warp::test::request()
+            .path(path)
+            .reply(&Routes::schemas().recover(handle_error))
+            .await;
+
+        assert_eq!(expected_status, resp.status());
+    }
+
+    #[rstest]
+    fn sse_event_test() {
+        let mut state = StateChange::with_buzz(Messages::Buzz {
+            author: "Tom".to_string(),
+        });
+
+        let event = BuzzHandlers::sse_event(state.clone()).to_string();
+        assert!(event.contains("event:NEW_BUZZ\n"), "{}", event);
+        assert!(!event.contains("id:"), "{}", event);
+
+        state.id = Some(7);
+
+        let event = BuzzHandlers::sse_event(state).to_string();
+        assert!(event.contains("id:7\n"), "{}", event);
+        assert!(event.contains(r#"data:{"type":"NEW_BUZZ""#), "{}", event);
+    }
+
+    #[rstest]
+    async fn metrics_test(game_info: Arc<Mutex<GameInfo>>) {
+        let resp = warp::test::request()
+            .path("/metrics")
+            .reply(&Routes::metrics(game_info))
+            .await;
+
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(
+            "text/plain; version=0.0.4",
+            resp.headers()["content-type"].to_str().unwrap()
+        );
+
+        // the global gauges are shared with the other tests, only the format is checked
+        let body = String::from_utf8(resp.body().to_vec()).unwrap();
+        assert!(
+            body.contains("# TYPE buzz_connected_players gauge"),
+            "{}",
+            body
+        );
+    }
+
+    #[rstest]
+    #[case("/game", "/game")]
+    #[case("/api/v1/game/buzz", "/game/buzz")]
+    #[case("/game/answer/", "/game/answer")]
+    #[case("/api/v1/schemas/Messages.json", "/schemas/{name}.json")]
+    #[case("/metrics", "/metrics")]
+    #[case("/game/unknown", "other")]
+    fn route_name_test(#[case] path: &str, #[case] expected: &str) {
+        assert_eq!(expected, route_name(path));
+    }
+
+    #[rstest]
+    async fn health_test() {
+        let resp = warp::test::request()
+            .path("/health")
+            .reply(&Routes::health())
+            .await;
+
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(r#"{"type":"ALIVE"}"#, resp.body());
+    }
+
+    #[rstest]
+    #[case(vec![Messages::None], StatusCode::OK, "READY")]
+    #[case(vec![], StatusCode::SERVICE_UNAVAILABLE, "NOT_READY")]
+    async fn ready_test(
+        #[case] questions: Vec<Messages>,
+        #[case] expected_status: StatusCode,
+        #[case] expected_fragment: &str,
+        service: SharedService,
+    ) {
+        let game_info = game_info(questions);
+
+        let resp = warp::test::request()
+            .path("/ready")
+            .reply(&Routes::ready(service, game_info).recover(handle_error))
+            .await;
+
+        assert_eq!(expected_status, resp.status());
+        assert!(String::from_utf8(resp.body().to_vec())
+            .unwrap()
+            .contains(expected_fragment));
+    }
+
+    #[rstest]
+    fn rate_limiter_test() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            lockout_threshold: 3,
+            ..RateLimitConfig::default()
+        });
+        let limit = Limit {
+            burst: 2,
+            refill_interval: Duration::from_secs(1),
+        };
+        let now = Instant::now();
+
+        // the burst is accepted, then a token is given back every second
+        assert!(limiter.check_at("ip:1.2.3.4", limit, now).is_ok());
+        assert!(limiter.check_at("ip:1.2.3.4", limit, now).is_ok());
+        assert_eq!(
+            Err(Duration::from_secs(1)),
+            limiter.check_at("ip:1.2.3.4", limit, now)
+        );
+        assert!(limiter.check_at("player:Tom", limit, now).is_ok());
+
+        let now = now + Duration::from_secs(1);
+        assert!(limiter.check_at("ip:1.2.3.4", limit, now).is_ok());
+
+        // insisting without token locks the client out
+        assert!(limiter.check_at("ip:1.2.3.4", limit, now).is_err());
+        assert!(limiter.check_at("ip:1.2.3.4", limit, now).is_err());
+        assert_eq!(
+            Err(Duration::from_secs(30)),
+            limiter.check_at("ip:1.2.3.4", limit, now)
+        );
+        assert_eq!(
+            Err(Duration::from_secs(20)),
+            limiter.check_at("ip:1.2.3.4", limit, now + Duration::from_secs(10))
+        );
+        assert!(limiter
+            .check_at("ip:1.2.3.4", limit, now + Duration::from_secs(30))
+            .is_ok());
+    }
+
+    #[rstest]
+    #[case(RateLimitConfig::default(), true)]
+    #[case(RateLimitConfig { ip_burst: 0, ..RateLimitConfig::default() }, true)]
+    #[case(RateLimitConfig { ip_burst: 10, ..RateLimitConfig::default() }, false)]
+    fn rate_limiter_players_behind_ip_test(
+        #[case] config: RateLimitConfig,
+        #[case] all_accepted: bool,
+    ) {
+        let limiter = RateLimiter::new(config);
+        let ip = "1.2.3.4".parse().unwrap();
+
+        // 10 players behind a same ip send their burst of 5 requests
+        let accepted = (0..10)
+            .flat_map(|player| std::iter::repeat_n(format!("Player {}", player), 5))
+            .filter(|name| limiter.check_ip(ip).and(limiter.check_player(name)).is_ok())
+            .count();
+
+        assert_eq!(all_accepted, accepted == 50, "{} accepted", accepted);
+    }
+
+    #[rstest]
+    fn rate_limiter_max_clients_test() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            max_clients: 2,
+            ..RateLimitConfig::default()
+        });
+        let limit = Limit {
+            burst: 1,
+            refill_interval: Duration::from_secs(60),
+        };
+        let now = Instant::now();
+
+        assert!(limiter.check_at("player:Tom", limit, now).is_ok());
+        assert!(limiter.check_at("player:Tim", limit, now).is_ok());
+        assert!(limiter.check_at("player:Tom", limit, now).is_err());
+
+        // a new client makes the least recently seen one forgotten
+        assert!(limiter.check_at("player:Joe", limit, now).is_ok());
+        assert_eq!(2, limiter.clients());
+
+        assert!(limiter.check_at("player:Tom", limit, now).is_err());
+        assert!(limiter.check_at("player:Tim", limit, now).is_ok());
+    }
+
+    #[rstest]
+    #[case(Duration::from_millis(1), 1)]
+    #[case(Duration::from_secs(2), 2)]
+    #[case(Duration::from_millis(2_100), 3)]
+    fn retry_after_seconds_test(#[case] wait: Duration, #[case] expected: u64) {
+        assert_eq!(expected, retry_after_seconds(wait));
+    }
+
+    #[rstest]
+    async fn rate_limited_buzz_test(service: SharedService, game_info: Arc<Mutex<GameInfo>>) {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            burst: 1,
+            ..RateLimitConfig::default()
+        });
+
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        game_info
+            .lock()
+            .await
+            .add_player("Tom".to_string(), tx)
+            .await;
+
+        let routes = Routes::versioned(service, game_info, limiter.clone()).recover(handle_error);
+
+        let buzz = |name: &str| {
+            warp::test::request()
+                .method("POST")
+                .path("/api/v1/game/buzz")
+                .remote_addr("127.0.0.1:4000".parse().unwrap())
+                .body(format!(r#"{{"type":"BUZZ","playerName":"{}"}}"#, name))
+        };
+
+        // a name unknown to the game is only limited by ip, it gets no bucket
+        for _ in 0..3 {
+            let resp = buzz("Nobody").reply(&routes).await;
+            assert_ne!(StatusCode::TOO_MANY_REQUESTS, resp.status());
+        }
+        assert_eq!(1, limiter.clients());
+
+        let resp = buzz("Tom").reply(&routes).await;
+        assert_ne!(StatusCode::TOO_MANY_REQUESTS, resp.status());
+
+        let resp = buzz("Tom").reply(&routes).await;
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, resp.status());
+        assert_eq!("1", resp.headers()["retry-after"].to_str().unwrap());
+        assert!(String::from_utf8(resp.body().to_vec())
+            .unwrap()
+            .contains("TOO_MANY_REQUESTS"));
+    }
+}
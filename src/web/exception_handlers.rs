@@ -1,40 +1,152 @@
 use std::convert::Infallible;
 
+use warp::body::BodyDeserializeError;
+use warp::http::header::RETRY_AFTER;
 use warp::http::StatusCode;
+use warp::reject::{
+    InvalidHeader, InvalidQuery, LengthRequired, MethodNotAllowed, MissingHeader, PayloadTooLarge,
+    UnsupportedMediaType,
+};
+use warp::ws::MissingConnectionUpgrade;
 use warp::{Rejection, Reply};
 
 use crate::dto::responses::Response;
+use crate::errors::error::ErrorCode;
 use crate::CustomError;
 
 pub async fn handle_error(error: Rejection) -> Result<impl Reply, Infallible> {
-    let mut status_code = StatusCode::INTERNAL_SERVER_ERROR;
-    let mut msg = String::new();
-
-    if let Some(e) = error.find::<CustomError>() {
-        msg = format!("{}", e.to_string());
-        match e {
-            CustomError::CreateDBPoolError(_) => {}
-            CustomError::GetDBConnectionError(_) => {}
-            CustomError::ExecuteDBQueryError { .. } => {}
-            CustomError::OpenFileError(_) => {}
-            CustomError::ReadFileError(_) => {}
-            CustomError::YamlDeserializationError(_) => {}
-            CustomError::PlayerAlreadyExistWithNameError(_) => {}
-            CustomError::PlayerNotFoundWithNameError(_) => {}
-            CustomError::SendEventError(_) => {
-                status_code = StatusCode::BAD_GATEWAY;
-            }
-            CustomError::BadRequestTypeError { .. } => {
-                status_code = StatusCode::BAD_REQUEST;
-            }
-        }
-    }
+    let (status_code, code, msg) = error_of(&error);
+    let params = error
+        .find::<CustomError>()
+        .map(CustomError::params)
+        .unwrap_or_default();
 
-    Ok(warp::reply::with_status(
-        warp::reply::json(&Response::Error {
-            message: msg,
-            code: status_code.as_u16(),
-        }),
+    let mut reply = warp::reply::with_status(
+        warp::reply::json(&Response::error(status_code.as_u16(), code, msg, params)),
         status_code,
-    ))
+    )
+    .into_response();
+
+    // a rate limited client is told when to retry
+    if let Some(CustomError::TooManyRequestsError { retry_after }) = error.find::<CustomError>() {
+        reply
+            .headers_mut()
+            .insert(RETRY_AFTER, (*retry_after).into());
+    }
+
+    Ok(reply)
+}
+
+/// ##Http status, code and message of a rejection
+///
+/// The errors of the application are looked up first, then the rejections of warp filters,
+/// an unknown path being the least specific one
+pub fn error_of(rejection: &Rejection) -> (StatusCode, ErrorCode, String) {
+    if let Some(e) = rejection.find::<CustomError>() {
+        (status_of(e), e.code(), e.to_string())
+    } else if let Some(e) = rejection.find::<InvalidQuery>() {
+        (
+            StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidQuery,
+            e.to_string(),
+        )
+    } else if let Some(e) = rejection.find::<BodyDeserializeError>() {
+        (
+            StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidBody,
+            e.to_string(),
+        )
+    } else if let Some(e) = rejection.find::<MissingHeader>() {
+        (
+            StatusCode::BAD_REQUEST,
+            ErrorCode::BadRequest,
+            e.to_string(),
+        )
+    } else if let Some(e) = rejection.find::<InvalidHeader>() {
+        (
+            StatusCode::BAD_REQUEST,
+            ErrorCode::BadRequest,
+            e.to_string(),
+        )
+    } else if let Some(e) = rejection.find::<MissingConnectionUpgrade>() {
+        (
+            StatusCode::BAD_REQUEST,
+            ErrorCode::BadRequest,
+            e.to_string(),
+        )
+    } else if let Some(e) = rejection.find::<LengthRequired>() {
+        (
+            StatusCode::LENGTH_REQUIRED,
+            ErrorCode::BadRequest,
+            e.to_string(),
+        )
+    } else if let Some(e) = rejection.find::<PayloadTooLarge>() {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::PayloadTooLarge,
+            e.to_string(),
+        )
+    } else if let Some(e) = rejection.find::<UnsupportedMediaType>() {
+        (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ErrorCode::UnsupportedMediaType,
+            e.to_string(),
+        )
+    } else if let Some(e) = rejection.find::<MethodNotAllowed>() {
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            ErrorCode::MethodNotAllowed,
+            e.to_string(),
+        )
+    } else if rejection.is_not_found() {
+        (
+            StatusCode::NOT_FOUND,
+            ErrorCode::NotFound,
+            "Not found".to_string(),
+        )
+    } else {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::InternalError,
+            format!("Unhandled rejection: {:?}", rejection),
+        )
+    }
+}
+
+/// Http status matching an error
+pub fn status_of(error: &CustomError) -> StatusCode {
+    match error {
+        CustomError::SendEventError(_) => StatusCode::BAD_GATEWAY,
+        CustomError::NotReadyError(_) => StatusCode::SERVICE_UNAVAILABLE,
+        CustomError::TooManyRequestsError { .. } => StatusCode::TOO_MANY_REQUESTS,
+        CustomError::BadRequestTypeError { .. }
+        | CustomError::InvalidBodyError { .. }
+        | CustomError::WrongQuestionNumberError { .. } => StatusCode::BAD_REQUEST,
+        CustomError::NotBuzzAuthorError(_) | CustomError::PlayerMismatchError { .. } => {
+            StatusCode::FORBIDDEN
+        }
+        CustomError::PlayerNotFoundWithNameError(_) => StatusCode::NOT_FOUND,
+        CustomError::GameAlreadyStartedError
+        | CustomError::GameFullError(_)
+        | CustomError::BuzzTakenError
+        | CustomError::PlayerAlreadyBuzzedError(_)
+        | CustomError::NoCurrentQuestionError
+        | CustomError::PlayerAlreadyExistWithNameError(_)
+        | CustomError::PlayerAlreadyConnectedError(_) => StatusCode::CONFLICT,
+        CustomError::CreateDBPoolError(_)
+        | CustomError::GetDBConnectionError(_)
+        | CustomError::ExecuteDBQueryError { .. }
+        | CustomError::BlockingTaskError(_)
+        | CustomError::DBTlsError(_)
+        | CustomError::InvalidConfigError(_)
+        | CustomError::ProfileNotFoundError(_)
+        | CustomError::UnsupportedDBKindError(_)
+        | CustomError::OpenFileError(_)
+        | CustomError::ReadFileError(_)
+        | CustomError::YamlDeserializationError(_)
+        | CustomError::JsonError(_)
+        | CustomError::MetricsError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        #[cfg(feature = "sqlite")]
+        CustomError::SqliteError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
 }
@@ -1,99 +1,522 @@
+use std::collections::VecDeque;
 use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use futures_util::SinkExt;
+use tokio::sync::mpsc::UnboundedReceiver;
 use tokio::sync::Mutex;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tokio_stream::StreamExt;
+use tracing::{error, instrument, Instrument};
+use warp::http::HeaderValue;
 use warp::sse::Event;
+use warp::ws::{Message, WebSocket, Ws};
 use warp::{reject, sse, Rejection};
 
-use crate::dto::requests::{AddPlayerQuery, Requests};
+use crate::data::storage::{GameStorage, PlayerStorage};
+use crate::dto::requests::{
+    AddPlayerQuery, Deprecation, LeaderboardQuery, ListPlayersQuery, ReceivedRequest, Requests,
+};
 use crate::dto::responses::Response;
-use crate::{BuzzService, GameInfo, Messages, StateChange};
+use crate::web::exception_handlers::status_of;
+use crate::web::rate_limit::RateLimiter;
+use crate::{BuzzService, CustomError, GameInfo, Messages, StateChange};
 
 pub struct BuzzHandlers {}
 
 impl BuzzHandlers {
-    pub async fn add_player(
-        service: Arc<Mutex<BuzzService>>,
+    #[instrument(skip(service, game_info, query), fields(player = %query.player))]
+    pub async fn add_player<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
         game_info: Arc<Mutex<GameInfo>>,
         query: AddPlayerQuery,
+        last_event_id: Option<u64>,
     ) -> Result<impl warp::Reply, Rejection> {
         let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
 
+        let mut service_guard = service.lock().await;
+
+        // a browser reconnecting sends the id of the last event it has received
+        let resp = match last_event_id {
+            Some(id) => {
+                service_guard
+                    .resume_player(query.to_request(), id, game_info.clone(), tx)
+                    .await
+            }
+            None => {
+                service_guard
+                    .add_player(query.to_request(), game_info.clone(), tx)
+                    .await
+            }
+        }
+        .map_err(|e| reject::custom(e))?;
+
+        drop(service_guard);
+
+        let rx = UnboundedReceiverStream::new(rx);
+
+        let stream = rx.map(|state| Ok::<Event, Infallible>(Self::sse_event(state)));
+
+        if let Response::PlayerAdded(true) = resp {
+            Self::start_game(service, game_info);
+        }
+
+        Ok(sse::reply(sse::keep_alive().stream(stream)))
+    }
+
+    /// ## Server sent event carrying a state change
+    ///
+    /// The event is named after the state change type, and identified by the state change id
+    pub fn sse_event(state: StateChange) -> Event {
+        let data = serde_json::to_string(&state).unwrap();
+
+        let event = sse::Event::default()
+            .event(state.change_type.event_name())
+            .data(data);
+
+        match state.id {
+            Some(id) => event.id(id.to_string()),
+            None => event,
+        }
+    }
+
+    /// ## Connect a player through a websocket
+    ///
+    /// The websocket carries the state changes to the player,
+    /// and the buzz and answer requests from the player
+    #[instrument(skip_all, fields(player = %query.player))]
+    pub async fn connect_ws<P: PlayerStorage, G: GameStorage>(
+        ws: Ws,
+        service: Arc<Mutex<BuzzService<P, G>>>,
+        game_info: Arc<Mutex<GameInfo>>,
+        query: AddPlayerQuery,
+        limiter: RateLimiter,
+        addr: Option<SocketAddr>,
+    ) -> Result<impl warp::Reply, Rejection> {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<StateChange>();
+
+        // a player whose websocket has dropped takes the resume path, the events sent on a websocket
+        // have no id so the history is replayed from its start
+        let resp = service
+            .lock()
+            .await
+            .resume_player(query.to_request(), 0, game_info.clone(), tx)
+            .await
+            .map_err(reject::custom)?;
+
+        if let Response::PlayerAdded(true) = resp {
+            Self::start_game(service.clone(), game_info.clone());
+        }
+
+        Ok(ws.on_upgrade(move |socket| {
+            let client = WsClient {
+                player_name: query.player,
+                ip: addr.map(|addr| addr.ip()),
+                limiter,
+            };
+
+            Self::handle_socket(socket, service, game_info, client, rx)
+        }))
+    }
+
+    #[instrument(skip_all, fields(player = received.request.player_name()))]
+    pub async fn register_buzz<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
+        game_info: Arc<Mutex<GameInfo>>,
+        received: ReceivedRequest,
+    ) -> Result<impl warp::Reply, Rejection> {
         let resp = service
             .lock()
             .await
-            .add_player(query.to_request(), game_info.clone(), tx)
+            .register_buzz(received.request, game_info.clone(), Duration::ZERO)
             .await
             .map_err(|e| reject::custom(e))?;
 
-        let rx = UnboundedReceiverStream::new(rx);
+        Ok(Self::with_deprecation(
+            warp::reply::json(&resp),
+            received.deprecation,
+        ))
+    }
 
-        let stream = rx.map(|state| {
-            let data = serde_json::to_string(&state).unwrap();
-            Ok::<Event, Infallible>(sse::Event::default().data(data))
-        });
+    #[instrument(skip_all, fields(player = received.request.player_name()))]
+    pub async fn register_answer<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
+        game_info: Arc<Mutex<GameInfo>>,
+        received: ReceivedRequest,
+    ) -> Result<impl warp::Reply, Rejection> {
+        let resp = service
+            .lock()
+            .await
+            .register_answer(received.request, game_info.clone())
+            .await
+            .map_err(|e| reject::custom(e))?;
+
+        Self::end_turn(service, game_info, resp);
 
-        if let Response::PlayerAdded(ready) = resp {
-            if ready {
-                let g_i = game_info.clone();
+        Ok(Self::with_deprecation(
+            warp::reply::json(&Response::AnswerRegistered),
+            received.deprecation,
+        ))
+    }
 
-                tokio::spawn(async move {
-                    // start the game by sending starting message
-                    g_i.lock().await.send(Messages::GameStart).await;
+    /// ###Warn the client that its request uses a deprecated form
+    ///
+    /// The `Deprecation` and `Warning` headers are added to the reply
+    ///
+    /// __reply__ : reply to the request
+    ///
+    /// __deprecation__ : deprecated form used by the request, if any
+    fn with_deprecation(
+        reply: impl warp::Reply,
+        deprecation: Option<Deprecation>,
+    ) -> warp::reply::Response {
+        let mut reply = warp::Reply::into_response(reply);
 
-                    std::thread::sleep(Duration::from_secs(1));
+        if let Some(deprecation) = deprecation {
+            let warning = format!("299 - \"{}\"", deprecation.message());
 
-                    // send next question
-                    g_i.lock().await.next_question().await;
-                });
+            if let Ok(warning) = HeaderValue::from_str(&warning) {
+                let headers = reply.headers_mut();
+                headers.insert("deprecation", HeaderValue::from_static("true"));
+                headers.insert("warning", warning);
             }
         }
 
-        Ok(sse::reply(sse::keep_alive().stream(stream)))
+        reply
     }
 
-    pub async fn register_buzz(
-        service: Arc<Mutex<BuzzService>>,
-        game_info: Arc<Mutex<GameInfo>>,
-        request: Requests,
+    #[instrument(skip_all)]
+    pub async fn list_players<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
+        query: ListPlayersQuery,
     ) -> Result<impl warp::Reply, Rejection> {
         let resp = service
             .lock()
             .await
-            .register_buzz(request, game_info.clone())
+            .list_players(query)
             .await
-            .map_err(|e| reject::custom(e))?;
+            .map_err(reject::custom)?;
+
+        Ok(warp::reply::json(&resp))
+    }
+
+    #[instrument(skip_all)]
+    pub async fn leaderboard<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
+        query: LeaderboardQuery,
+    ) -> Result<impl warp::Reply, Rejection> {
+        let resp = service
+            .lock()
+            .await
+            .leaderboard(query)
+            .await
+            .map_err(reject::custom)?;
 
         Ok(warp::reply::json(&resp))
     }
 
-    pub async fn register_answer(
-        service: Arc<Mutex<BuzzService>>,
+    #[instrument(skip_all)]
+    pub async fn ready<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
         game_info: Arc<Mutex<GameInfo>>,
-        request: Requests,
     ) -> Result<impl warp::Reply, Rejection> {
         let resp = service
             .lock()
             .await
-            .register_answer(request, game_info.clone())
+            .ready(game_info)
             .await
-            .map_err(|e| reject::custom(e))?;
+            .map_err(reject::custom)?;
+
+        Ok(warp::reply::json(&resp))
+    }
+
+    /// Start the game by sending the starting message, then the first question
+    fn start_game<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
+        game_info: Arc<Mutex<GameInfo>>,
+    ) {
+        tokio::spawn(
+            async move {
+                // start the game by sending starting message
+                game_info.lock().await.send(Messages::GameStart).await;
+
+                std::thread::sleep(Duration::from_secs(1));
+
+                // send next question
+                if let Err(e) = service.lock().await.next_question(game_info).await {
+                    error!(error = %e, "Sending the first question failed");
+                }
+            }
+            .in_current_span(),
+        );
+    }
+
+    /// Send the updated score of an answer, then pass to the next turn
+    fn end_turn<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
+        game_info: Arc<Mutex<GameInfo>>,
+        resp: Response,
+    ) {
+        tokio::spawn(
+            async move {
+                // send updated score, then the next question or the turn of the next player in the buzz queue
+                let result = if let Response::ScoreUpdated(score) = resp {
+                    let good_answer = matches!(score, Messages::PlayerScore { update: true, .. });
+
+                    game_info.lock().await.send(score).await;
+
+                    service.lock().await.next_turn(game_info, good_answer).await
+                } else {
+                    //send next question
+                    service.lock().await.next_question(game_info).await
+                };
+
+                if let Err(e) = result {
+                    error!(error = %e, "Passing to the next turn failed");
+                }
+            }
+            .in_current_span(),
+        );
+    }
+
+    /// Forward the state changes to the websocket and handle the requests it receives,
+    /// until one of both sides is closed
+    ///
+    /// The player round trip time is measured with pings, and used to arbitrate the buzzes
+    #[instrument(skip(socket, service, game_info, client, rx), fields(player = %client.player_name))]
+    async fn handle_socket<P: PlayerStorage, G: GameStorage>(
+        socket: WebSocket,
+        service: Arc<Mutex<BuzzService<P, G>>>,
+        game_info: Arc<Mutex<GameInfo>>,
+        client: WsClient,
+        rx: UnboundedReceiver<StateChange>,
+    ) {
+        let player_name = client.player_name.clone();
+
+        let (latencies, ping_interval) = {
+            let game_info = game_info.lock().await;
+            (game_info.latencies.clone(), game_info.ping_interval)
+        };
+
+        let (mut ws_tx, mut ws_rx) = futures_util::StreamExt::split(socket);
+        let mut events = UnboundedReceiverStream::new(rx);
+        let mut pings =
+            tokio::time::interval_at(tokio::time::Instant::now() + ping_interval, ping_interval);
+        let mut pending_pings = PendingPings::default();
+
+        loop {
+            let reply = tokio::select! {
+                event = events.next() => match event {
+                    Some(state) => serde_json::to_string(&state).map(Message::text),
+                    None => break,
+                },
+                _ = pings.tick() => Ok(Message::ping(pending_pings.ping(Instant::now()))),
+                message = ws_rx.next() => match message {
+                    Some(Ok(message)) if message.is_text() => {
+                        // the buzz is compensated by the latency measured on this connection
+                        let rtt = latencies
+                            .lock()
+                            .await
+                            .get(&player_name)
+                            .cloned()
+                            .unwrap_or_default();
+
+                        let (resp, deprecation) = Self::handle_ws_request(
+                            service.clone(),
+                            game_info.clone(),
+                            &client,
+                            rtt,
+                            message.to_str().unwrap_or_default(),
+                        )
+                        .await;
+
+                        // the client is warned before receiving the response to its request
+                        if let Some(deprecation) = deprecation {
+                            let sent = match serde_json::to_string(&Response::warning(deprecation)) {
+                                Ok(warning) => ws_tx.send(Message::text(warning)).await.is_ok(),
+                                Err(_) => false,
+                            };
+
+                            if !sent {
+                                break;
+                            }
+                        }
+
+                        serde_json::to_string(&resp).map(Message::text)
+                    }
+                    Some(Ok(message)) if message.is_pong() => {
+                        // only the pongs answering a ping of this connection are measured
+                        if let Some(rtt) = pending_pings.pong(message.as_bytes(), Instant::now()) {
+                            // smooth the round trip time to absorb the network jitter
+                            let mut latencies = latencies.lock().await;
+                            let latency = latencies.entry(player_name.clone()).or_insert(rtt);
+                            *latency = (*latency * 3 + rtt) / 4;
+                        }
+                        continue;
+                    }
+                    Some(Ok(message)) if message.is_close() => break,
+                    Some(Ok(_)) => continue,
+                    _ => break,
+                },
+            };
+
+            let sent = match reply {
+                Ok(message) => ws_tx.send(message).await.is_ok(),
+                Err(_) => false,
+            };
+
+            if !sent {
+                break;
+            }
+        }
 
-        let g_i = game_info.clone();
+        latencies.lock().await.remove(&player_name);
 
-        tokio::spawn(async move {
-            // send updated score
-            if let Response::ScoreUpdated(score) = resp {
-                g_i.lock().await.send(score).await;
+        let _ = ws_tx.close().await;
+    }
+
+    /// Handle a buzz or an answer request received from a websocket,
+    /// if the client is within its rate limits
+    ///
+    /// _return_ the response to send back, an error response if the request failed,
+    /// with the deprecated form used by the request if any
+    #[instrument(skip_all)]
+    async fn handle_ws_request<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
+        game_info: Arc<Mutex<GameInfo>>,
+        client: &WsClient,
+        rtt: Duration,
+        text: &str,
+    ) -> (Response, Option<Deprecation>) {
+        let mut deprecation = None;
+
+        let request = client
+            .check_rate_limits()
+            .and_then(|_| Requests::from_json(text))
+            .and_then(|received| {
+                deprecation = received.deprecation;
+                client.check_identity(received.request)
+            });
+
+        let resp = match request {
+            Ok(request @ Requests::RegisterBuzz { .. }) => {
+                service
+                    .lock()
+                    .await
+                    .register_buzz(request, game_info, rtt)
+                    .await
+            }
+            Ok(request @ Requests::RegisterAnswer { .. }) => {
+                let resp = service
+                    .lock()
+                    .await
+                    .register_answer(request, game_info.clone())
+                    .await;
+
+                resp.map(|resp| {
+                    Self::end_turn(service, game_info, resp);
+                    Response::AnswerRegistered
+                })
             }
+            Ok(request) => Err(CustomError::BadRequestTypeError {
+                message: format!("Unexpected request on websocket : {:?}", request),
+            }),
+            Err(e) => Err(e),
+        };
 
-            //send next question
-            g_i.lock().await.next_question().await;
+        let resp = resp.unwrap_or_else(|e| {
+            Response::error(status_of(&e).as_u16(), e.code(), e.to_string(), e.params())
         });
 
-        Ok(warp::reply::json(&Response::AnswerRegistered))
+        (resp, deprecation)
+    }
+}
+
+/// ##Player connected with a websocket
+///
+/// __player_name__ : name of the player given when connecting
+///
+/// __ip__ : ip of the client, if known
+///
+/// __limiter__ : rate limiter of the buzz and answer requests
+pub struct WsClient {
+    pub player_name: String,
+    pub ip: Option<IpAddr>,
+    pub limiter: RateLimiter,
+}
+
+impl WsClient {
+    /// ###Consume a token of the client ip, then of the player
+    fn check_rate_limits(&self) -> Result<(), CustomError> {
+        if let Some(ip) = self.ip {
+            self.limiter.check_ip(ip)?;
+        }
+
+        self.limiter.check_player(&self.player_name)
+    }
+
+    /// ###Check that the request is sent by the player of the connection
+    ///
+    /// __request__ : request received on the websocket
+    fn check_identity(&self, request: Requests) -> Result<Requests, CustomError> {
+        if request.player_name() == self.player_name {
+            Ok(request)
+        } else {
+            Err(CustomError::PlayerMismatchError {
+                expected: self.player_name.clone(),
+                found: request.player_name().to_string(),
+            })
+        }
+    }
+}
+
+// Maximum number of pings waiting for their pong, the older ones are considered lost
+const MAX_PENDING_PINGS: usize = 4;
+
+/// ##Pings sent on a websocket and not answered yet
+///
+/// The round trip time is measured from the time the server sent the ping,
+/// the pongs which do not echo an outstanding ping are ignored
+#[derive(Default)]
+pub struct PendingPings {
+    next: u64,
+    pending: VecDeque<(u64, Instant)>,
+}
+
+impl PendingPings {
+    /// ###Register a ping sent now
+    ///
+    /// __now__ : sending time of the ping
+    ///
+    /// _return_ the payload of the ping
+    pub fn ping(&mut self, now: Instant) -> Vec<u8> {
+        self.next += 1;
+
+        if self.pending.len() == MAX_PENDING_PINGS {
+            self.pending.pop_front();
+        }
+        self.pending.push_back((self.next, now));
+
+        self.next.to_be_bytes().to_vec()
+    }
+
+    /// ###Match a pong with its ping
+    ///
+    /// The matched ping and the ones sent before it are no longer awaited
+    ///
+    /// __payload__ : payload of the pong
+    ///
+    /// __now__ : receiving time of the pong
+    ///
+    /// _return_ the round trip time, None if the pong does not echo an outstanding ping
+    pub fn pong(&mut self, payload: &[u8], now: Instant) -> Option<Duration> {
+        let id = u64::from_be_bytes(<[u8; 8]>::try_from(payload).ok()?);
+        let position = self.pending.iter().position(|(ping, _)| *ping == id)?;
+
+        let (_, sent_at) = self.pending.drain(..=position).next_back()?;
+
+        Some(now.saturating_duration_since(sent_at))
     }
 }
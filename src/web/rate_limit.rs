@@ -0,0 +1,238 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::rate_limit::{Limit, RateLimitConfig};
+use crate::errors::error::CustomError;
+
+/// ##Token bucket of a client
+///
+/// __limit__ : size and refill rate of the bucket
+///
+/// __tokens__ : requests the client can still send at once
+///
+/// __refilled_at__ : last time the tokens were refilled
+///
+/// __rejections__ : requests rejected in a row
+///
+/// __locked_until__ : end of the lockout of the client, if locked out
+///
+/// __used_at__ : rank of the last request of the client, among all the requests
+#[derive(Debug, Clone)]
+struct Bucket {
+    limit: Limit,
+    tokens: f64,
+    refilled_at: Instant,
+    rejections: u32,
+    locked_until: Option<Instant>,
+    used_at: u64,
+}
+
+impl Bucket {
+    fn full(limit: Limit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst as f64,
+            refilled_at: now,
+            rejections: 0,
+            locked_until: None,
+            used_at: 0,
+        }
+    }
+
+    /// ###Give back the tokens earned since the last refill
+    fn refill(&mut self, now: Instant) {
+        let earned = match self.limit.refill_interval.as_secs_f64() {
+            interval if interval > 0.0 => {
+                now.saturating_duration_since(self.refilled_at)
+                    .as_secs_f64()
+                    / interval
+            }
+            _ => f64::INFINITY,
+        };
+
+        self.tokens = (self.tokens + earned).min(self.limit.burst as f64);
+        self.refilled_at = now;
+    }
+}
+
+/// ##Buckets of the clients, bounded in number
+///
+/// __buckets__ : bucket of each client
+///
+/// __recent__ : clients by rank of their last request, the least recently seen first
+///
+/// __requests__ : number of requests seen
+#[derive(Debug, Default)]
+struct Buckets {
+    buckets: HashMap<String, Bucket>,
+    recent: BTreeMap<u64, String>,
+    requests: u64,
+}
+
+impl Buckets {
+    /// ###Bucket of a client, marked as the most recently seen
+    ///
+    /// The least recently seen client is forgotten if a new client exceeds the number of clients kept
+    ///
+    /// __key__ : client key
+    ///
+    /// __capacity__ : number of clients kept
+    ///
+    /// __new_bucket__ : bucket of a new client
+    fn touch(
+        &mut self,
+        key: &str,
+        capacity: usize,
+        new_bucket: impl FnOnce() -> Bucket,
+    ) -> &mut Bucket {
+        self.requests += 1;
+
+        match self.buckets.get(key) {
+            Some(bucket) => {
+                self.recent.remove(&bucket.used_at);
+            }
+            None => {
+                while self.buckets.len() >= capacity.max(1) {
+                    match self.recent.pop_first() {
+                        Some((_, oldest)) => self.buckets.remove(&oldest),
+                        None => break,
+                    };
+                }
+            }
+        }
+
+        self.recent.insert(self.requests, key.to_string());
+
+        let bucket = self
+            .buckets
+            .entry(key.to_string())
+            .or_insert_with(new_bucket);
+        bucket.used_at = self.requests;
+
+        bucket
+    }
+
+    fn len(&self) -> usize {
+        self.buckets.len()
+    }
+}
+
+/// ##Rate limiter of the clients requests
+///
+/// The buckets are shared by the clones of the limiter
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    /// ###Create a rate limiter
+    ///
+    /// __config__ : rate limiting configuration
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Arc::new(Mutex::new(Buckets::default())),
+        }
+    }
+
+    /// ##Consume a token of a player
+    ///
+    /// Fails with TooManyRequestsError, carrying the time to wait, if the player has no token left
+    /// or is locked out
+    ///
+    /// __name__ : the player name
+    pub fn check_player(&self, name: &str) -> Result<(), CustomError> {
+        let limit = self.config.player_limit();
+
+        Self::retry_after(self.check_at(&format!("player:{}", name), limit, Instant::now()))
+    }
+
+    /// ##Consume a token of an ip
+    ///
+    /// Fails with TooManyRequestsError, carrying the time to wait, if the ip has no token left
+    /// or is locked out. Every request is accepted if the requests are not limited by ip
+    ///
+    /// __ip__ : the client ip
+    pub fn check_ip(&self, ip: IpAddr) -> Result<(), CustomError> {
+        match self.config.ip_limit() {
+            Some(limit) => {
+                Self::retry_after(self.check_at(&format!("ip:{}", ip), limit, Instant::now()))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// ###Number of clients whose bucket is kept
+    pub fn clients(&self) -> usize {
+        self.buckets.lock().unwrap().len()
+    }
+
+    fn retry_after(result: Result<(), Duration>) -> Result<(), CustomError> {
+        result.map_err(|wait| CustomError::TooManyRequestsError {
+            retry_after: retry_after_seconds(wait),
+        })
+    }
+
+    /// ##Consume a token of a client at a given time
+    ///
+    /// __key__ : client key, such as its ip or its player name
+    ///
+    /// __limit__ : limit of the kind of the client
+    ///
+    /// __now__ : time of the request
+    ///
+    /// _return_ the time to wait before the next request if the request is rejected
+    pub fn check_at(&self, key: &str, limit: Limit, now: Instant) -> Result<(), Duration> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+
+        let config = &self.config;
+        let mut buckets = self.buckets.lock().unwrap();
+
+        let bucket = buckets.touch(key, config.max_clients, || Bucket::full(limit, now));
+
+        if let Some(until) = bucket.locked_until {
+            if now < until {
+                return Err(until - now);
+            }
+
+            bucket.locked_until = None;
+        }
+
+        bucket.refill(now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            bucket.rejections = 0;
+            return Ok(());
+        }
+
+        bucket.rejections += 1;
+
+        // a client insisting while it has no token left is locked out
+        if config.lockout_threshold > 0 && bucket.rejections >= config.lockout_threshold {
+            bucket.rejections = 0;
+            bucket.locked_until = Some(now + config.lockout());
+
+            tracing::warn!(client = key, lockout = ?config.lockout(), "Client locked out");
+
+            return Err(config.lockout());
+        }
+
+        Err(limit.refill_interval.mul_f64(1.0 - bucket.tokens))
+    }
+}
+
+/// ##Time to wait in whole seconds, as sent in the Retry-After header
+///
+/// __wait__ : time to wait before the next request
+pub fn retry_after_seconds(wait: Duration) -> u64 {
+    let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+
+    seconds.max(1)
+}
@@ -1,49 +1,312 @@
 use std::convert::Infallible;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
+use serde::de::DeserializeOwned;
 use tokio::sync::Mutex;
-use warp::{get, path, post, Filter, Rejection, Reply};
+use warp::hyper::body::Bytes;
+use warp::{get, path, post, reject, Filter, Rejection, Reply};
 
-use crate::dto::requests::AddPlayerQuery;
+use crate::data::storage::{GameStorage, PlayerStorage};
+use crate::dto::requests::{
+    AddPlayerQuery, LeaderboardQuery, ListPlayersQuery, ReceivedRequest, RegisterAnswerBody,
+    RegisterBuzzBody,
+};
+use crate::dto::responses::Response;
+use crate::dto::schemas::{openapi_schemas, schema};
+use crate::errors::error::CustomError;
+use crate::metrics::METRICS;
 use crate::web::handlers::BuzzHandlers;
+use crate::web::rate_limit::RateLimiter;
 use crate::{BuzzService, GameInfo};
 
+// OpenAPI description of the api paths, the component schemas are generated
+const OPENAPI: &str = include_str!("openapi.json");
+
+// Paths of the api, used as route labels of the metrics
+const ROUTE_NAMES: [&str; 10] = [
+    "/game",
+    "/game/ws",
+    "/game/buzz",
+    "/game/answer",
+    "/game/players",
+    "/game/leaderboard",
+    "/openapi.json",
+    "/metrics",
+    "/health",
+    "/ready",
+];
+
+/// ##Route of a request path, as labelled in the metrics
+///
+/// The version prefix is removed and the unknown paths are grouped, to keep the number of labels bounded
+///
+/// __path__ : path of the request
+pub fn route_name(path: &str) -> &'static str {
+    let path = path.strip_prefix("/api/v1").unwrap_or(path);
+
+    if path.starts_with("/schemas/") {
+        return "/schemas/{name}.json";
+    }
+
+    ROUTE_NAMES
+        .iter()
+        .find(|route| **route == path.trim_end_matches('/'))
+        .copied()
+        .unwrap_or("other")
+}
+
+/// ##OpenAPI description of the api
+///
+/// The paths are described by openapi.json, the component schemas are generated from the DTOs
+/// so that they can not drift from what is sent and received
+pub fn openapi_document() -> Result<serde_json::Value, CustomError> {
+    let mut doc: serde_json::Value = serde_json::from_str(OPENAPI)?;
+    doc["components"]["schemas"] = serde_json::to_value(openapi_schemas())?;
+
+    Ok(doc)
+}
+
 pub struct Routes {}
 
 impl Routes {
-    pub fn add_player(
-        service: Arc<Mutex<BuzzService>>,
+    /// ##Api mounted under /api/v1
+    ///
+    /// The unversioned paths are kept as aliases
+    pub fn versioned<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
+        game_info: Arc<Mutex<GameInfo>>,
+        limiter: RateLimiter,
+    ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+        let api = Self::api(service, game_info, limiter);
+
+        path!("api" / "v1" / ..)
+            .and(api.clone().or(Self::openapi()).or(Self::schemas()))
+            .or(api)
+    }
+
+    /// ##Routes of the game api
+    ///
+    /// The buzz and answer requests are rate limited
+    pub fn api<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
+        game_info: Arc<Mutex<GameInfo>>,
+        limiter: RateLimiter,
+    ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+        Self::add_player(service.clone(), game_info.clone())
+            .or(Self::websocket(
+                service.clone(),
+                game_info.clone(),
+                limiter.clone(),
+            ))
+            .or(Self::register_buzz(
+                service.clone(),
+                game_info.clone(),
+                limiter.clone(),
+            ))
+            .or(Self::register_answer(service.clone(), game_info, limiter))
+            .or(Self::list_players(service.clone()))
+            .or(Self::leaderboard(service))
+    }
+
+    /// ##JSON schemas of the DTOs exchanged with the players
+    ///
+    /// Served as `/schemas/<name>.json`
+    pub fn schemas() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+        path!("schemas" / String)
+            .and(get())
+            .and_then(|file: String| async move {
+                file.strip_suffix(".json")
+                    .and_then(schema)
+                    .map(|schema| warp::reply::json(&schema))
+                    .ok_or_else(reject::not_found)
+            })
+    }
+
+    /// ##Prometheus metrics of the application
+    ///
+    /// Served at the root, outside of the versioned api
+    pub fn metrics(
+        game_info: Arc<Mutex<GameInfo>>,
+    ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+        path!("metrics")
+            .and(get())
+            .and(Self::with_game_info(game_info))
+            .and_then(|game_info: Arc<Mutex<GameInfo>>| async move {
+                let game_info = game_info.lock().await;
+
+                METRICS
+                    .render(&game_info)
+                    .await
+                    .map(|metrics| {
+                        warp::reply::with_header(
+                            metrics,
+                            "content-type",
+                            "text/plain; version=0.0.4",
+                        )
+                    })
+                    .map_err(reject::custom)
+            })
+    }
+
+    /// ##Liveness probe
+    ///
+    /// Answers as long as the process is alive
+    pub fn health() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+        path!("health")
+            .and(get())
+            .map(|| warp::reply::json(&Response::Alive))
+    }
+
+    /// ##Readiness probe
+    ///
+    /// Answers 503 while the storage or the question bank is not ready
+    pub fn ready<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
+        game_info: Arc<Mutex<GameInfo>>,
+    ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+        path!("ready")
+            .and(get())
+            .and(Self::with_service(service))
+            .and(Self::with_game_info(game_info))
+            .and_then(BuzzHandlers::ready)
+    }
+
+    /// ##OpenAPI description of the api
+    pub fn openapi() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+        path!("openapi.json").and(get()).and_then(|| async {
+            openapi_document()
+                .map(|doc| warp::reply::json(&doc))
+                .map_err(reject::custom)
+        })
+    }
+
+    pub fn add_player<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
         game_info: Arc<Mutex<GameInfo>>,
     ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
-        crate::routes!(get -> service, game_info, |s, g, q| async {
-            BuzzHandlers::add_player(s,g, q).await
+        crate::routes!(get -> service, game_info, |s, g, q, l| async move {
+            BuzzHandlers::add_player(s, g, q, l).await
         })
     }
 
-    pub fn register_buzz(
-        service: Arc<Mutex<BuzzService>>,
+    /// ##Websocket of a player
+    ///
+    /// The buzz and answer requests it receives are rate limited by player and by the ip of the client
+    pub fn websocket<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
         game_info: Arc<Mutex<GameInfo>>,
+        limiter: RateLimiter,
     ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
-        crate::routes!(post -> "buzz", service, game_info, |s, g, r| async {
+        crate::routes!(ws -> "ws", service, game_info, limiter, |w, s, g, q, l, a| async move {
+            BuzzHandlers::connect_ws(w, s, g, q, l, a).await
+        })
+    }
+
+    pub fn register_buzz<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
+        game_info: Arc<Mutex<GameInfo>>,
+        limiter: RateLimiter,
+    ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+        crate::routes!(post -> "buzz", service, game_info, limiter, RegisterBuzzBody, |s, g, r| async {
             BuzzHandlers::register_buzz(s, g, r).await
         })
     }
 
-    pub fn register_answer(
-        service: Arc<Mutex<BuzzService>>,
+    pub fn register_answer<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
         game_info: Arc<Mutex<GameInfo>>,
+        limiter: RateLimiter,
     ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
-        crate::routes!(post -> "answer", service, game_info, |s, g, r| async {
+        crate::routes!(post -> "answer", service, game_info, limiter, RegisterAnswerBody, |s, g, r| async {
             BuzzHandlers::register_answer(s, g, r).await
         })
     }
 
-    pub fn with_service(
-        service: Arc<Mutex<BuzzService>>,
-    ) -> impl Filter<Extract = (Arc<Mutex<BuzzService>>,), Error = Infallible> + Clone {
+    pub fn list_players<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
+    ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+        crate::routes!(get -> "players", service, ListPlayersQuery, |s, q| async {
+            BuzzHandlers::list_players(s, q).await
+        })
+    }
+
+    pub fn leaderboard<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
+    ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+        crate::routes!(get -> "leaderboard", service, LeaderboardQuery, |s, q| async {
+            BuzzHandlers::leaderboard(s, q).await
+        })
+    }
+
+    pub fn with_service<P: PlayerStorage, G: GameStorage>(
+        service: Arc<Mutex<BuzzService<P, G>>>,
+    ) -> impl Filter<Extract = (Arc<Mutex<BuzzService<P, G>>>,), Error = Infallible> + Clone {
         warp::any().map(move || service.clone())
     }
 
+    /// ###Request sent as json body
+    ///
+    /// A body which can not be deserialized is rejected with the field at fault,
+    /// as well as a body whose type is not the one of the route
+    pub fn request_body<T>() -> impl Filter<Extract = (ReceivedRequest,), Error = Rejection> + Clone
+    where
+        T: DeserializeOwned + TryInto<ReceivedRequest, Error = CustomError> + Send,
+    {
+        warp::body::bytes().and_then(|body: Bytes| async move {
+            let deserializer = &mut serde_json::Deserializer::from_slice(&body);
+
+            serde_path_to_error::deserialize::<_, T>(deserializer)
+                .map_err(CustomError::from)
+                .and_then(T::try_into)
+                .map_err(reject::custom)
+        })
+    }
+
+    /// ###Request sent as json body, by a client within its rate limits
+    ///
+    /// The client ip is checked before reading the body, then the player named in the request.
+    /// A player not registered in the game has no bucket, its requests are only limited by ip
+    pub fn rate_limited_body<T>(
+        limiter: RateLimiter,
+        game_info: Arc<Mutex<GameInfo>>,
+    ) -> impl Filter<Extract = (ReceivedRequest,), Error = Rejection> + Clone
+    where
+        T: DeserializeOwned + TryInto<ReceivedRequest, Error = CustomError> + Send,
+    {
+        let ip_limiter = limiter.clone();
+
+        warp::addr::remote()
+            .and_then(move |addr: Option<SocketAddr>| {
+                let limiter = ip_limiter.clone();
+                async move {
+                    addr.map_or(Ok(()), |addr| limiter.check_ip(addr.ip()))
+                        .map_err(reject::custom)
+                }
+            })
+            .untuple_one()
+            .and(Self::request_body::<T>())
+            .and_then(move |request: ReceivedRequest| {
+                let limiter = limiter.clone();
+                let game_info = game_info.clone();
+                async move {
+                    let name = request.request.player_name();
+
+                    if game_info.lock().await.is_registered(name).await {
+                        limiter.check_player(name).map_err(reject::custom)?;
+                    }
+
+                    Ok::<_, Rejection>(request)
+                }
+            })
+    }
+
+    pub fn with_limiter(
+        limiter: RateLimiter,
+    ) -> impl Filter<Extract = (RateLimiter,), Error = Infallible> + Clone {
+        warp::any().map(move || limiter.clone())
+    }
+
     pub fn with_game_info(
         game: Arc<Mutex<GameInfo>>,
     ) -> impl Filter<Extract = (Arc<Mutex<GameInfo>>,), Error = Infallible> + Clone {
@@ -1,11 +1,19 @@
 #[macro_export]
 macro_rules! routes {
-    (post -> $path: expr, $service:ident, $game_info: ident, $f: expr) => {{
+    (post -> $path: expr, $service:ident, $game_info: ident, $limiter: ident, $body: ty, $f: expr) => {{
         path!("game" / $path)
             .and(post())
             .and(Routes::with_service($service))
-            .and(Routes::with_game_info($game_info))
-            .and(warp::body::json())
+            .and(Routes::with_game_info($game_info.clone()))
+            .and(Routes::rate_limited_body::<$body>($limiter, $game_info))
+            .and_then($f)
+    }};
+
+    (get -> $path: expr, $service: ident, $query: ty, $f: expr) => {{
+        path!("game" / $path)
+            .and(get())
+            .and(Routes::with_service($service))
+            .and(warp::query::<$query>())
             .and_then($f)
     }};
 
@@ -15,6 +23,17 @@ macro_rules! routes {
             .and(Routes::with_service($service))
             .and(Routes::with_game_info($game_info))
             .and(warp::query::<AddPlayerQuery>())
+            .and(warp::header::optional::<u64>("last-event-id"))
+            .and_then($f)
+    }};
+    (ws -> $path: expr, $service: ident, $game_info: ident, $limiter: ident, $f: expr) => {{
+        path!("game" / $path)
+            .and(warp::ws())
+            .and(Routes::with_service($service))
+            .and(Routes::with_game_info($game_info))
+            .and(warp::query::<AddPlayerQuery>())
+            .and(Routes::with_limiter($limiter))
+            .and(warp::addr::remote())
             .and_then($f)
     }};
 }